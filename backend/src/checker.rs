@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use crate::parser::{compound_operator, operator_symbol, split_index, ErrorMessage, MAX_NESTING, Expr, ExprNode, ProgramNode, Stmt, StmtNode};
+use crate::symbols::{ScopeKind, Symbol, SymbolKind, SymbolTable};
+use crate::token::{Span, TokenType};
+use crate::types::{arithmetic_type, describe, is_assignable, is_integral, rank};
+
+// The value of an index written as an integer literal, possibly negated.
+fn constant_index(index: &Expr) -> Option<i64> {
+    match &index.node {
+        ExprNode::IntLiteral(value) => Some(*value as i64),
+        ExprNode::Unary(TokenType::Minus, operand) => constant_index(operand).map(|value| -value),
+        _ => None,
+    }
+}
+
+// Gives every expression in the program its type, reporting operands that do
+// not fit their operator and values that do not fit where they are stored.
+// Names are expected to have been resolved already; an expression involving a
+// name that does not resolve is left untyped and not reported again.
+pub fn check(program: &mut ProgramNode) -> Vec<ErrorMessage> {
+    let mut checker = Checker {
+        table: SymbolTable::new(Span::default()),
+        functions: HashMap::new(),
+        function: None,
+        errors: Vec::new(),
+        depth: 0,
+    };
+    for statement in &program.statements {
+        if let StmtNode::Function(return_type, name, parameters, _) = &statement.node {
+            let parameter_types = parameters.iter().map(|(parameter_type, _)| parameter_type.clone()).collect();
+            checker.functions.insert(name.clone(), (return_type.clone(), parameter_types));
+            checker.declare(Symbol::new(name, SymbolKind::Function, return_type, statement.span));
+        }
+    }
+    // Every prototype has to agree with the definition, or the first prototype
+    for statement in &program.statements {
+        if let StmtNode::Prototype(return_type, name, parameter_types) = &statement.node {
+            let signature = (return_type.clone(), parameter_types.clone());
+            match checker.functions.get(name) {
+                Some(existing) if *existing != signature => {
+                    checker.error(&format!("Conflicting declarations of function '{}'", name), statement.span);
+                },
+                Some(_) => (),
+                None => {
+                    checker.functions.insert(name.clone(), signature);
+                    checker.declare(Symbol::new(name, SymbolKind::Function, return_type, statement.span));
+                },
+            }
+        }
+    }
+    for statement in &mut program.statements {
+        checker.statement(statement);
+    }
+    checker.errors
+}
+
+struct Checker {
+    table: SymbolTable,
+    // Return type and parameter types of every function
+    functions: HashMap<String, (TokenType, Vec<TokenType>)>,
+    // Return type and name of the function being checked
+    function: Option<(TokenType, String)>,
+    errors: Vec<ErrorMessage>,
+    // How many expressions enclose the one being checked
+    depth: usize,
+}
+
+impl Checker {
+    fn error(&mut self, message: &str, span: Span) {
+        self.errors.push(ErrorMessage::new(message, "Error", span));
+    }
+
+    // Duplicates have already been reported while resolving names
+    fn declare(&mut self, symbol: Symbol) {
+        let _ = self.table.declare(symbol);
+    }
+
+    fn statement(&mut self, statement: &mut Stmt) {
+        match &mut statement.node {
+            StmtNode::Declaration(variable_type, name, value) => {
+                if let Some(value) = value {
+                    self.assignment(variable_type, value);
+                }
+                let symbol = Symbol::new(name, SymbolKind::Variable, variable_type, statement.span);
+                self.declare(symbol);
+            },
+            StmtNode::ListDeclaration(element_type, name, dimensions, values) => {
+                for (_, value) in values.iter_mut() {
+                    self.assignment(element_type, value);
+                }
+                let dimensions = dimensions.clone();
+                let symbol = Symbol { dimensions, ..Symbol::new(name, SymbolKind::List, element_type, statement.span) };
+                self.declare(symbol);
+            },
+            StmtNode::Declarations(declarations) => declarations.iter_mut().for_each(|declaration| self.statement(declaration)),
+            StmtNode::Expression(expr) => self.discarded(expr),
+            StmtNode::ForLoop(initialization, condition, increment, body) => {
+                self.table.enter(ScopeKind::For, Span::default());
+                if let Some(initialization) = initialization {
+                    self.statement(initialization);
+                }
+                if let Some(condition) = condition {
+                    self.condition(condition);
+                }
+                if let Some(increment) = increment {
+                    self.discarded(increment);
+                }
+                self.statement(body);
+                self.table.exit();
+            },
+            StmtNode::IfStatement(condition, then_branch, else_branch) => {
+                self.condition(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            },
+            StmtNode::WhileLoop(condition, body) => {
+                self.condition(condition);
+                self.statement(body);
+            },
+            StmtNode::DoWhileLoop(body, condition) => {
+                self.statement(body);
+                self.condition(condition);
+            },
+            StmtNode::SwitchCase(subject, cases) => {
+                if let Some(subject_type) = self.value(subject).filter(|subject_type| !is_integral(subject_type)) {
+                    self.error(&format!("Switch value must be an integer, not '{:?}'", subject_type), subject.span);
+                }
+                self.table.enter(ScopeKind::Switch, Span::default());
+                for (label, statements) in cases {
+                    if let Some(label) = label {
+                        if let Some(label_type) = self.value(label).filter(|label_type| !is_integral(label_type)) {
+                            self.error(&format!("Case label must be an integer, not '{:?}'", label_type), label.span);
+                        }
+                    }
+                    statements.iter_mut().for_each(|statement| self.statement(statement));
+                }
+                self.table.exit();
+            },
+            StmtNode::Block(statements) => {
+                self.table.enter(ScopeKind::Block, Span::default());
+                statements.iter_mut().for_each(|statement| self.statement(statement));
+                self.table.exit();
+            },
+            StmtNode::Return(Some(value)) => {
+                let value_type = self.value(value);
+                if let (Some(value_type), Some((return_type, function))) = (value_type, &self.function) {
+                    if !is_assignable(return_type, &value_type) {
+                        let message = format!("Cannot return {} from function '{}' of type '{:?}'", describe(&value_type), function, return_type);
+                        self.error(&message, value.span);
+                    }
+                }
+            },
+            StmtNode::Function(return_type, name, parameters, body) => {
+                self.function = Some((return_type.clone(), name.clone()));
+                self.table.enter(ScopeKind::Function, Span::default());
+                for (parameter_type, parameter) in parameters.iter() {
+                    self.declare(Symbol::new(parameter, SymbolKind::Parameter, parameter_type, statement.span));
+                }
+                match &mut body.node {
+                    StmtNode::Block(statements) => statements.iter_mut().for_each(|statement| self.statement(statement)),
+                    _ => self.statement(body),
+                }
+                self.table.exit();
+                self.function = None;
+            },
+            StmtNode::Return(None) | StmtNode::Prototype(..) | StmtNode::Break | StmtNode::Continue | StmtNode::Empty => (),
+        }
+    }
+
+    // Types every index of an indexed list, including those of the partly
+    // indexed lists inside it such as `grid[i]` in `grid[i][j]`.
+    fn indexes(&mut self, expr: &mut Expr) {
+        if let ExprNode::Index(list, index) = &mut expr.node {
+            self.indexes(list);
+            if self.value(index).is_some_and(|index_type| !is_integral(&index_type)) {
+                self.error("Expected an integer index", index.span);
+            }
+        }
+    }
+
+    // The element type of a fully indexed list. Indexes known before running
+    // must already be in bounds.
+    fn element(&mut self, expr: &Expr) -> Option<TokenType> {
+        let (list, indexes) = split_index(expr);
+        let symbol = match &list.node {
+            ExprNode::Variable(name) => self.table.lookup(name).filter(|symbol| symbol.kind == SymbolKind::List).cloned()?,
+            _ => return None,
+        };
+        if indexes.len() != symbol.dimensions.len() {
+            let message = format!("List '{}' expects {} index(es) but got {}", symbol.name, symbol.dimensions.len(), indexes.len());
+            self.error(&message, expr.span);
+            return None;
+        }
+        for (index, length) in indexes.into_iter().zip(&symbol.dimensions) {
+            if let Some(position) = constant_index(index) {
+                if usize::try_from(position).map_or(true, |position| position >= *length) {
+                    let message = format!("Index {} is out of bounds for '{}' of size {}", position, symbol.name, length);
+                    self.error(&message, index.span);
+                }
+            }
+        }
+        Some(symbol.symbol_type)
+    }
+
+    // Checks a value stored into something of type `target`.
+    fn assignment(&mut self, target: &TokenType, value: &mut Expr) {
+        if let Some(value_type) = self.value(value) {
+            if !is_assignable(target, &value_type) {
+                let message = format!("Cannot assign {} to a variable of type '{:?}'", describe(&value_type), target);
+                self.error(&message, value.span);
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &mut Expr) {
+        if let Some(condition_type) = self.value(condition).filter(|condition_type| rank(condition_type).is_none()) {
+            let message = format!("Condition of type '{:?}' is neither a number nor a boolean", condition_type);
+            self.error(&message, condition.span);
+        }
+    }
+
+    // Types an expression whose value is used, which a void call does not have.
+    fn value(&mut self, expr: &mut Expr) -> Option<TokenType> {
+        let value_type = self.expression(expr)?;
+        if value_type == TokenType::Void {
+            // The value of `a, f()` is that of the call at the end
+            let mut used = &*expr;
+            while let ExprNode::Binary(_, TokenType::Comma, right) = &used.node {
+                used = right;
+            }
+            if let ExprNode::Call(function, _) = &used.node {
+                let message = format!("Function '{}' does not return a value", function);
+                self.error(&message, used.span);
+            }
+            return None;
+        }
+        Some(value_type)
+    }
+
+    // Types an expression whose value is thrown away, as in a statement of its
+    // own or left of a ','. A call there may have no value.
+    fn discarded(&mut self, expr: &mut Expr) {
+        match expr.node {
+            ExprNode::Call(..) | ExprNode::Binary(_, TokenType::Comma, _) => {
+                self.expression(expr);
+            },
+            _ => {
+                self.value(expr);
+            },
+        }
+    }
+
+    fn expression(&mut self, expr: &mut Expr) -> Option<TokenType> {
+        if self.depth >= MAX_NESTING {
+            self.error("Nesting is too deep", expr.span);
+            return None;
+        }
+        self.depth += 1;
+        let expr_type = self.expression_type(expr);
+        self.depth -= 1;
+        expr_type
+    }
+
+    fn expression_type(&mut self, expr: &mut Expr) -> Option<TokenType> {
+        let span = expr.span;
+        if let ExprNode::Index(..) = expr.node {
+            self.indexes(expr);
+            let element_type = self.element(expr);
+            expr.expr_type = element_type.clone();
+            return element_type;
+        }
+        let expr_type = match &mut expr.node {
+            ExprNode::IntLiteral(_) => Some(TokenType::Int),
+            ExprNode::FloatLiteral(_) => Some(TokenType::Float),
+            ExprNode::DoubleLiteral(_) => Some(TokenType::Double),
+            ExprNode::CharLiteral(_) => Some(TokenType::Char),
+            ExprNode::StringLiteral(_) => Some(TokenType::String),
+            ExprNode::BoolLiteral(_) => Some(TokenType::Bool),
+            ExprNode::Variable(name) => self.table.lookup(name)
+                .filter(|symbol| matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Parameter))
+                .map(|symbol| symbol.symbol_type.clone()),
+            ExprNode::Index(..) => None,
+            ExprNode::Unary(operator, operand) | ExprNode::Postfix(operand, operator) => {
+                let operator = operator.clone();
+                let operand_type = self.value(operand)?;
+                let result = match operator {
+                    TokenType::LogicalNot => rank(&operand_type).map(|_| TokenType::Bool),
+                    TokenType::BitwiseNot if is_integral(&operand_type) => Some(TokenType::Int),
+                    TokenType::Minus => arithmetic_type(&operand_type, &operand_type),
+                    // `++` and `--` keep the type of what they update
+                    TokenType::PlusPlus | TokenType::MinusMinus => rank(&operand_type).map(|_| operand_type.clone()),
+                    _ => None,
+                };
+                if result.is_none() {
+                    let message = format!("Operator '{}' cannot be applied to a value of type '{:?}'", operator_symbol(&operator), operand_type);
+                    self.error(&message, span);
+                }
+                result
+            },
+            ExprNode::Binary(left, TokenType::Comma, right) => {
+                self.discarded(left);
+                self.expression(right)
+            },
+            ExprNode::Binary(left, operator, right) => {
+                let operator = operator.clone();
+                let (left_type, right_type) = (self.value(left), self.value(right));
+                let (left_type, right_type) = (left_type?, right_type?);
+                self.binary(&operator, &left_type, &right_type, span)
+            },
+            ExprNode::Ternary(condition, then_expr, else_expr) => {
+                self.condition(condition);
+                let (then_type, else_type) = (self.value(then_expr), self.value(else_expr));
+                let (then_type, else_type) = (then_type?, else_type?);
+                let result = if then_type == else_type { Some(then_type.clone()) } else { arithmetic_type(&then_type, &else_type) };
+                if result.is_none() {
+                    let message = format!("Operands of '?:' have incompatible types '{:?}' and '{:?}'", then_type, else_type);
+                    self.error(&message, span);
+                }
+                result
+            },
+            ExprNode::Assign(target, operator, value) => {
+                let operator = operator.clone();
+                let (target_type, value_type) = (self.value(target), self.value(value));
+                let (target_type, value_type) = (target_type?, value_type?);
+                // A compound assignment stores the result of its operator
+                let stored_type = match compound_operator(&operator) {
+                    Some(arithmetic) => self.binary(&arithmetic, &target_type, &value_type, span)?,
+                    None => value_type,
+                };
+                if !is_assignable(&target_type, &stored_type) {
+                    let message = format!("Cannot assign {} to a variable of type '{:?}'", describe(&stored_type), target_type);
+                    self.error(&message, value.span);
+                }
+                Some(target_type)
+            },
+            ExprNode::Call(function, arguments) => {
+                let Some((return_type, parameter_types)) = self.functions.get(function.as_str()).cloned() else {
+                    arguments.iter_mut().for_each(|argument| { self.value(argument); });
+                    return None;
+                };
+                if arguments.len() != parameter_types.len() {
+                    let message = format!("Function '{}' expects {} argument(s) but got {}", function, parameter_types.len(), arguments.len());
+                    self.error(&message, span);
+                }
+                for (position, argument) in arguments.iter_mut().enumerate() {
+                    let argument_type = self.value(argument);
+                    if let (Some(argument_type), Some(parameter_type)) = (argument_type, parameter_types.get(position)) {
+                        if !is_assignable(parameter_type, &argument_type) {
+                            let message = format!(
+                                "Argument {} of '{}' expects type '{:?}' but got {}",
+                                position + 1, function, parameter_type, describe(&argument_type),
+                            );
+                            self.error(&message, argument.span);
+                        }
+                    }
+                }
+                Some(return_type)
+            },
+        };
+        expr.expr_type = expr_type.clone();
+        expr_type
+    }
+
+    // The type of a binary operation after the usual arithmetic conversions.
+    fn binary(&mut self, operator: &TokenType, left: &TokenType, right: &TokenType, span: Span) -> Option<TokenType> {
+        let is_string = |value_type: &TokenType| *value_type == TokenType::String;
+        let result = match operator {
+            // Strings concatenate with strings and chars
+            TokenType::Plus if is_string(left) || is_string(right) => {
+                let joinable = |value_type: &TokenType| is_string(value_type) || *value_type == TokenType::Char;
+                (joinable(left) && joinable(right)).then_some(TokenType::String)
+            },
+            TokenType::Plus | TokenType::Minus | TokenType::Multiply | TokenType::Divide => arithmetic_type(left, right),
+            TokenType::Modulo | TokenType::BitwiseAnd | TokenType::BitwiseOr | TokenType::BitwiseXor
+            | TokenType::ShiftLeft | TokenType::ShiftRight => {
+                (is_integral(left) && is_integral(right)).then_some(TokenType::Int)
+            },
+            // Strings compare with strings, numbers with numbers
+            TokenType::Equal | TokenType::NotEqual | TokenType::LessThan | TokenType::LessThanOrEqual
+            | TokenType::GreaterThan | TokenType::GreaterThanOrEqual => {
+                let comparable = (is_string(left) && is_string(right)) || arithmetic_type(left, right).is_some();
+                comparable.then_some(TokenType::Bool)
+            },
+            TokenType::LogicalAnd | TokenType::LogicalOr => (rank(left).is_some() && rank(right).is_some()).then_some(TokenType::Bool),
+            _ => None,
+        };
+        if result.is_none() {
+            let message = format!(
+                "Operator '{}' cannot be applied to values of type '{:?}' and '{:?}'",
+                operator_symbol(operator), left, right,
+            );
+            self.error(&message, span);
+        }
+        result
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Serialize, Deserialize};
+use crate::scanner::{Scanner, ScannerOptions};
+use crate::token::Token;
+
+// Replaces the bytes `start..end` of the previous source with `replacement`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Relexed {
+    pub source: String,
+    pub tokens: Vec<Token>,
+    // Number of tokens that were scanned again rather than carried over
+    pub relexed: usize,
+}
+
+// Applies `edit` to `source` and brings `previous`, the tokens scanned from it
+// with the same `options`, up to date.
+//
+// Tokens ending before the edited line are kept and scanning resumes right
+// after the last of them. A block comment may run over several lines, but one
+// reaching into the edited line ends past its start and is scanned again from
+// its opening "/*"; a string stops at the end of its line even when it is
+// unterminated. Deciding where a token ends looks at no more than the newline
+// after it, so none of the kept tokens depended on the edited text. Once
+// a fresh token past the edit starts where an old token started, the rest of the
+// text is identical and so are the remaining tokens; they are reused with their
+// spans shifted.
+pub fn relex(source: &str, previous: &[Token], edit: &TextEdit, options: &ScannerOptions) -> Result<Relexed, String> {
+    let valid = edit.start <= edit.end
+        && edit.end <= source.len()
+        && source.is_char_boundary(edit.start)
+        && source.is_char_boundary(edit.end);
+    if !valid {
+        return Err(format!("Edit range {}..{} is not valid for a source of {} bytes", edit.start, edit.end, source.len()));
+    }
+    let line_start = source[..edit.start].rfind('\n').map_or(0, |newline| newline + 1);
+    let kept = previous.partition_point(|token| token.span.end < line_start);
+    let (restart, line, column) = match kept.checked_sub(1) {
+        Some(last) => (previous[last].span.end, previous[last].span.end_line, previous[last].span.end_column),
+        None => (0, 1, 1),
+    };
+
+    let new_source = format!("{}{}{}", &source[..edit.start], edit.replacement, &source[edit.end..]);
+    let new_end = edit.start + edit.replacement.len();
+    let old_end_position = advance_position((line, column), &source[restart..edit.end]);
+    let new_end_position = advance_position((line, column), &new_source[restart..new_end]);
+    let delta = new_end as isize - edit.end as isize;
+
+    let mut tokens = previous[..kept].to_vec();
+    let mut old = kept;
+    let mut relexed = 0;
+    for token in Scanner::resume(new_source.clone(), options.clone(), restart, line, column) {
+        if token.span.start >= new_end {
+            let old_start = (token.span.start as isize - delta) as usize;
+            while old < previous.len() && previous[old].span.start < old_start {
+                old += 1;
+            }
+            if old < previous.len() && previous[old].span.start == old_start {
+                let shifted = previous[old..].iter()
+                    .map(|token| shift(token.clone(), delta, old_end_position, new_end_position));
+                tokens.extend(shifted);
+                return Ok(Relexed { source: new_source, tokens, relexed });
+            }
+        }
+        tokens.push(token);
+        relexed += 1;
+    }
+    Ok(Relexed { source: new_source, tokens, relexed })
+}
+
+fn advance_position((mut line, mut column): (usize, usize), text: &str) -> (usize, usize) {
+    for c in text.chars() {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+// Moves a token that followed the edit to its place in the new source. Only
+// tokens on the line where the edit ended change column.
+fn shift(mut token: Token, delta: isize, (old_line, old_column): (usize, usize), (new_line, new_column): (usize, usize)) -> Token {
+    let span = &mut token.span;
+    span.start = (span.start as isize + delta) as usize;
+    span.end = (span.end as isize + delta) as usize;
+    if span.line == old_line {
+        span.column = span.column - old_column + new_column;
+    }
+    if span.end_line == old_line {
+        span.end_column = span.end_column - old_column + new_column;
+    }
+    span.line = span.line - old_line + new_line;
+    span.end_line = span.end_line - old_line + new_line;
+    token
+}
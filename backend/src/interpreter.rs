@@ -0,0 +1,785 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use crate::parser::{compound_operator, operator_symbol, split_index, ErrorMessage, Expr, ExprNode, ProgramNode, Stmt, StmtNode};
+use crate::token::{Span, TokenType};
+use crate::types::{arithmetic_type, describe, is_assignable};
+use serde::{Deserialize, Serialize};
+
+// Upper bound on executed statements, so a program that never terminates still
+// gets an answer.
+const MAX_STEPS: usize = 1_000_000;
+// Filling an array costs a step for every this many elements, so allocating
+// large arrays over and over still runs into MAX_STEPS.
+const ELEMENTS_PER_STEP: usize = 16;
+// Upper bound on the array elements alive at once, so deep recursion over a
+// large local array cannot exhaust memory.
+const MAX_ELEMENTS: usize = 1 << 21;
+// Upper bound on nested function calls, so runaway recursion is reported
+// instead of overflowing the stack.
+const MAX_CALL_DEPTH: usize = 1000;
+// Every call nests several evaluation frames, so programs run on a thread whose
+// stack has room for the deepest allowed recursion even in debug builds.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+    Double(f64),
+    Char(char),
+    Bool(bool),
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Double(value) => write!(f, "{}", value),
+            Value::Char(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Value {
+    // The value of a variable declared without an initializer, zero like a C global
+    fn zero(variable_type: &TokenType) -> Self {
+        match variable_type {
+            TokenType::Float => Value::Float(0.0),
+            TokenType::Double => Value::Double(0.0),
+            TokenType::Char => Value::Char('\0'),
+            TokenType::Bool => Value::Bool(false),
+            TokenType::String => Value::String(String::new()),
+            _ => Value::Int(0),
+        }
+    }
+
+    fn value_type(&self) -> TokenType {
+        match self {
+            Value::Int(_) => TokenType::Int,
+            Value::Float(_) => TokenType::Float,
+            Value::Double(_) => TokenType::Double,
+            Value::Char(_) => TokenType::Char,
+            Value::Bool(_) => TokenType::Bool,
+            Value::String(_) => TokenType::String,
+        }
+    }
+
+    fn as_int(&self) -> Option<i32> {
+        match self {
+            Value::Int(value) => Some(*value),
+            Value::Char(value) => Some(*value as i32),
+            Value::Bool(value) => Some(*value as i32),
+            _ => None,
+        }
+    }
+
+    fn as_double(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value as f64),
+            Value::Double(value) => Some(*value),
+            _ => self.as_int().map(f64::from),
+        }
+    }
+
+    fn truth(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            Value::String(_) => None,
+            _ => self.as_double().map(|value| value != 0.0),
+        }
+    }
+}
+
+// How control leaves a statement.
+#[derive(Debug, Clone, PartialEq)]
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Option<Value>),
+}
+
+// An array stored in row-major order.
+struct List {
+    element_type: TokenType,
+    dimensions: Vec<usize>,
+    values: Vec<Value>,
+}
+
+// The elements of a list as reported back, nested once per dimension.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ListValue {
+    Element(String),
+    Row(Vec<ListValue>),
+}
+
+// Runs `task` on a thread with a `STACK_SIZE` stack and waits for its result.
+pub fn with_large_stack<T: Send>(task: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, task)
+            .expect("failed to start the interpreter thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+fn nest(values: &[Value], dimensions: &[usize]) -> Vec<ListValue> {
+    match dimensions {
+        [] | [_] => values.iter().map(|value| ListValue::Element(value.to_string())).collect(),
+        [_, inner @ ..] => {
+            let stride = inner.iter().product::<usize>().max(1);
+            values.chunks(stride).map(|row| ListValue::Row(nest(row, inner))).collect()
+        },
+    }
+}
+
+// The variables and lists declared in one block.
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, (TokenType, Value)>,
+    lists: HashMap<String, List>,
+}
+
+impl Scope {
+    fn contains(&self, name: &str) -> bool {
+        self.variables.contains_key(name) || self.lists.contains_key(name)
+    }
+}
+
+// Runs a parsed program, keeping the values of its variables and lists.
+pub struct Interpreter {
+    // The global scope followed by one scope per open block, innermost last
+    scopes: Vec<Scope>,
+    // Where the scopes of the running function start; a function sees its own
+    // blocks and the globals, never the locals of its caller
+    frame_start: usize,
+    functions: HashMap<String, Arc<Stmt>>,
+    depth: usize,
+    steps: usize,
+    // Elements of the arrays in all open scopes, up to MAX_ELEMENTS
+    elements: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope::default()],
+            frame_start: 0,
+            functions: HashMap::new(),
+            depth: 0,
+            steps: 0,
+            elements: 0,
+        }
+    }
+
+    // Executes the top-level statements in order, then `main` if the program
+    // defines one. A top-level statement that fails is reported and skipped, and
+    // the rest of the program still runs.
+    pub fn run(&mut self, program: &ProgramNode) -> Result<(), Vec<ErrorMessage>> {
+        with_large_stack(|| self.run_program(program))
+    }
+
+    fn run_program(&mut self, program: &ProgramNode) -> Result<(), Vec<ErrorMessage>> {
+        let mut errors = Vec::new();
+        for statement in &program.statements {
+            if let StmtNode::Function(_, name, _, _) = &statement.node {
+                if self.functions.insert(name.clone(), Arc::new(statement.clone())).is_some() {
+                    errors.push(ErrorMessage::new(&format!("Function '{}' already defined", name), "Error", statement.span));
+                }
+            }
+        }
+        for statement in &program.statements {
+            if let Err(error) = self.execute(statement) {
+                errors.push(error);
+                if self.steps > MAX_STEPS {
+                    break;
+                }
+            }
+        }
+        if let Some(main) = self.functions.get("main").cloned() {
+            // The frame of `main` is kept afterwards so its locals show up in the result
+            self.frame_start = self.scopes.len();
+            self.scopes.push(Scope::default());
+            match &main.node {
+                StmtNode::Function(_, _, parameters, _) if !parameters.is_empty() => {
+                    errors.push(ErrorMessage::new("Function 'main' cannot take parameters", "Error", main.span));
+                },
+                _ => {
+                    if let Err(error) = self.invoke(&main) {
+                        errors.push(error);
+                    }
+                },
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Variables still in scope, with inner declarations hiding outer ones.
+    pub fn get_declared_variables(&self) -> HashMap<String, (TokenType, String)> {
+        self.scopes.iter()
+            .flat_map(|scope| &scope.variables)
+            .map(|(name, (variable_type, value))| (name.clone(), (variable_type.clone(), value.to_string())))
+            .collect()
+    }
+
+    pub fn get_declared_lists(&self) -> HashMap<String, Vec<ListValue>> {
+        self.scopes.iter()
+            .flat_map(|scope| &scope.lists)
+            .map(|(name, list)| (name.clone(), nest(&list.values, &list.dimensions)))
+            .collect()
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> Result<Flow, ErrorMessage> {
+        self.charge(1, statement.span)?;
+
+        match &statement.node {
+            StmtNode::Declaration(variable_type, name, value) => {
+                let value = match value {
+                    Some(value) => {
+                        let value = self.evaluate(value)?;
+                        self.convert(value, variable_type, statement.span)?
+                    },
+                    None => Value::zero(variable_type),
+                };
+                self.declare(name, statement.span)?.variables.insert(name.clone(), (variable_type.clone(), value));
+                Ok(Flow::Normal)
+            },
+            StmtNode::ListDeclaration(element_type, name, dimensions, values) => {
+                let size: usize = dimensions.iter().product();
+                if self.elements + size > MAX_ELEMENTS {
+                    let message = format!("Array '{}' does not fit; all arrays together may hold at most {} elements", name, MAX_ELEMENTS);
+                    return Err(ErrorMessage::new(&message, "Error", statement.span));
+                }
+                self.charge(size / ELEMENTS_PER_STEP, statement.span)?;
+                // Elements without an initializer start out as zero
+                let mut elements = vec![Value::zero(element_type); size];
+                for (position, value) in values {
+                    let element = self.evaluate(value)?;
+                    elements[*position] = self.convert(element, element_type, value.span)?;
+                }
+                let list = List { element_type: element_type.clone(), dimensions: dimensions.clone(), values: elements };
+                self.declare(name, statement.span)?.lists.insert(name.clone(), list);
+                self.elements += size;
+                Ok(Flow::Normal)
+            },
+            // A call on its own may discard the result, or have none
+            StmtNode::Expression(expr) => self.discard(expr).map(|_| Flow::Normal),
+            StmtNode::Declarations(declarations) => self.execute_all(declarations),
+            StmtNode::Block(statements) => self.in_scope(|interpreter| interpreter.execute_all(statements)),
+            StmtNode::IfStatement(condition, then_branch, else_branch) => {
+                if self.condition(condition)? {
+                    self.execute(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)
+                } else {
+                    Ok(Flow::Normal)
+                }
+            },
+            StmtNode::WhileLoop(condition, body) => {
+                while self.condition(condition)? {
+                    match self.execute(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => (),
+                    }
+                }
+                Ok(Flow::Normal)
+            },
+            StmtNode::DoWhileLoop(body, condition) => {
+                loop {
+                    // `continue` skips to the condition check like the end of the body does
+                    match self.execute(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => (),
+                    }
+                    if !self.condition(condition)? {
+                        break;
+                    }
+                }
+                Ok(Flow::Normal)
+            },
+            StmtNode::ForLoop(initialization, condition, increment, body) => self.in_scope(|interpreter| {
+                if let Some(initialization) = initialization {
+                    interpreter.execute(initialization)?;
+                }
+                while condition.as_ref().map_or(Ok(true), |condition| interpreter.condition(condition))? {
+                    match interpreter.execute(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => (),
+                    }
+                    if let Some(increment) = increment {
+                        interpreter.discard(increment)?;
+                    }
+                }
+                Ok(Flow::Normal)
+            }),
+            StmtNode::SwitchCase(subject, cases) => {
+                let subject_value = self.evaluate(subject)?;
+                let mut matched = None;
+                for (position, (label, _)) in cases.iter().enumerate() {
+                    let Some(label) = label else { continue };
+                    let label_value = self.evaluate(label)?;
+                    if self.binary(&TokenType::Equal, subject_value.clone(), label_value, label.span)? == Value::Bool(true) {
+                        matched = Some(position);
+                        break;
+                    }
+                }
+                // `default:` is only taken when no case matches, wherever it is written
+                let Some(matched) = matched.or_else(|| cases.iter().position(|(label, _)| label.is_none())) else {
+                    return Ok(Flow::Normal);
+                };
+                // Execution falls through into the following cases until a `break`
+                let flow = self.in_scope(|interpreter| {
+                    // The whole body is one scope, so what the skipped cases declare
+                    // is still there, only without its initializer
+                    for (_, statements) in &cases[..matched] {
+                        statements.iter().try_for_each(|statement| interpreter.declare_skipped(statement))?;
+                    }
+                    for (_, statements) in &cases[matched..] {
+                        let flow = interpreter.execute_all(statements)?;
+                        if flow != Flow::Normal {
+                            return Ok(flow);
+                        }
+                    }
+                    Ok(Flow::Normal)
+                })?;
+                // A `continue` or `return` belongs to the code around the switch
+                Ok(if flow == Flow::Break { Flow::Normal } else { flow })
+            },
+            StmtNode::Break => Ok(Flow::Break),
+            StmtNode::Continue => Ok(Flow::Continue),
+            StmtNode::Return(value) => {
+                let value = value.as_ref().map(|value| self.evaluate(value)).transpose()?;
+                Ok(Flow::Return(value))
+            },
+            // Functions are collected before the program starts
+            StmtNode::Function(..) | StmtNode::Prototype(..) => Ok(Flow::Normal),
+            StmtNode::Empty => Ok(Flow::Normal),
+        }
+    }
+
+    // Runs statements in order until one of them transfers control elsewhere.
+    fn execute_all(&mut self, statements: &[Stmt]) -> Result<Flow, ErrorMessage> {
+        for statement in statements {
+            let flow = self.execute(statement)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // Declares what `statement` declares as if its initializers were left out.
+    fn declare_skipped(&mut self, statement: &Stmt) -> Result<(), ErrorMessage> {
+        let node = match &statement.node {
+            StmtNode::Declaration(variable_type, name, _) => StmtNode::Declaration(variable_type.clone(), name.clone(), None),
+            StmtNode::ListDeclaration(element_type, name, dimensions, _) => {
+                StmtNode::ListDeclaration(element_type.clone(), name.clone(), dimensions.clone(), Vec::new())
+            },
+            StmtNode::Declarations(declarations) => {
+                return declarations.iter().try_for_each(|declaration| self.declare_skipped(declaration));
+            },
+            _ => return Ok(()),
+        };
+        self.execute(&Stmt::new(node, statement.span)).map(|_| ())
+    }
+
+    // Calls a function with the values of `arguments`, converted to the
+    // parameter types the way assignment converts them.
+    fn call(&mut self, name: &str, arguments: &[Expr], span: Span) -> Result<Option<Value>, ErrorMessage> {
+        let Some(function) = self.functions.get(name).cloned() else {
+            return Err(ErrorMessage::new(&format!("Call to undeclared function '{}'", name), "Error", span));
+        };
+        let StmtNode::Function(_, _, parameters, _) = &function.node else {
+            unreachable!("only function definitions are collected");
+        };
+        if arguments.len() != parameters.len() {
+            let message = format!("Function '{}' expects {} argument(s) but got {}", name, parameters.len(), arguments.len());
+            return Err(ErrorMessage::new(&message, "Error", span));
+        }
+
+        let mut scope = Scope::default();
+        for (position, ((parameter_type, parameter), argument)) in parameters.iter().zip(arguments).enumerate() {
+            let value = self.evaluate(argument)?;
+            let description = describe(&value.value_type());
+            let value = self.convert(value, parameter_type, argument.span).map_err(|_| {
+                let message = format!("Argument {} of '{}' expects type '{:?}' but got {}", position + 1, name, parameter_type, description);
+                ErrorMessage::new(&message, "Error", argument.span)
+            })?;
+            scope.variables.insert(parameter.clone(), (parameter_type.clone(), value));
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            let message = format!("Too many nested calls to '{}'; the recursion may never end", name);
+            return Err(ErrorMessage::new(&message, "Error", span));
+        }
+
+        let caller_start = self.frame_start;
+        self.frame_start = self.scopes.len();
+        self.scopes.push(scope);
+        self.depth += 1;
+        let result = self.invoke(&function);
+        self.depth -= 1;
+        self.close_scopes(self.frame_start);
+        self.frame_start = caller_start;
+        result
+    }
+
+    // Runs the body of a function in the frame that was just opened for it.
+    // The parameters and the outermost block share that frame's first scope.
+    fn invoke(&mut self, function: &Stmt) -> Result<Option<Value>, ErrorMessage> {
+        let StmtNode::Function(return_type, name, _, body) = &function.node else {
+            unreachable!("only function definitions are invoked");
+        };
+        let flow = match &body.node {
+            StmtNode::Block(statements) => self.execute_all(statements)?,
+            _ => self.execute(body)?,
+        };
+        match flow {
+            Flow::Return(Some(value)) => self.convert(value, return_type, body.span).map(Some),
+            // Reaching the end of `main` returns 0
+            _ if name == "main" => Ok(Some(Value::Int(0))),
+            _ => Ok(None),
+        }
+    }
+
+    // Runs `body` in a fresh block scope, dropping whatever it declared afterwards.
+    fn in_scope<T>(&mut self, body: impl FnOnce(&mut Self) -> Result<T, ErrorMessage>) -> Result<T, ErrorMessage> {
+        self.scopes.push(Scope::default());
+        let depth = self.scopes.len();
+        let result = body(self);
+        self.close_scopes(depth - 1);
+        result
+    }
+
+    // Drops the scopes from `start` on, along with the arrays declared in them.
+    fn close_scopes(&mut self, start: usize) {
+        for scope in self.scopes.drain(start..) {
+            self.elements -= scope.lists.values().map(|list| list.values.len()).sum::<usize>();
+        }
+    }
+
+    // Counts `steps` towards MAX_STEPS, failing once the program has used them up.
+    fn charge(&mut self, steps: usize, span: Span) -> Result<(), ErrorMessage> {
+        self.steps += steps;
+        if self.steps > MAX_STEPS {
+            let message = format!("Execution stopped after {} steps; the program may never terminate", MAX_STEPS);
+            return Err(ErrorMessage::new(&message, "Error", span));
+        }
+        Ok(())
+    }
+
+    // Adds a name to the innermost scope, where it may hide a declaration from
+    // an enclosing one.
+    fn declare(&mut self, name: &str, span: Span) -> Result<&mut Scope, ErrorMessage> {
+        let scope = self.scopes.last_mut().expect("the global scope is never closed");
+        if scope.contains(name) {
+            return Err(ErrorMessage::new(&format!("Variable '{}' already declared", name), "Error", span));
+        }
+        Ok(scope)
+    }
+
+    // The innermost scope visible from the running function that declares `name`.
+    fn lookup(&self, name: &str) -> Option<usize> {
+        let globals = if self.frame_start > 0 { 0..1 } else { 0..0 };
+        (self.frame_start..self.scopes.len()).rev()
+            .chain(globals)
+            .find(|&index| self.scopes[index].contains(name))
+    }
+
+    fn variable(&self, name: &str) -> Option<&(TokenType, Value)> {
+        self.lookup(name).and_then(|index| self.scopes[index].variables.get(name))
+    }
+
+    fn list(&self, name: &str) -> Option<&List> {
+        self.lookup(name).and_then(|index| self.scopes[index].lists.get(name))
+    }
+
+    fn condition(&mut self, condition: &Expr) -> Result<bool, ErrorMessage> {
+        let value = self.evaluate(condition)?;
+        value.truth().ok_or_else(|| {
+            let message = format!("Condition of type '{:?}' is neither a number nor a boolean", value.value_type());
+            ErrorMessage::new(&message, "Error", condition.span)
+        })
+    }
+
+    // Converts a value for storage in a variable of `variable_type` the way C
+    // assignment does.
+    fn convert(&self, value: Value, variable_type: &TokenType, span: Span) -> Result<Value, ErrorMessage> {
+        if value.value_type() == *variable_type {
+            return Ok(value);
+        }
+        let converted = match (variable_type, &value) {
+            _ if !is_assignable(variable_type, &value.value_type()) => None,
+            // Floating values truncate toward zero
+            (TokenType::Int, value) => value.as_double().map(|value| Value::Int(value as i32)),
+            // A char holds a single byte, so wider values wrap around
+            (TokenType::Char, value) => value.as_double().map(|value| Value::Char(value as i32 as u8 as char)),
+            (TokenType::Float, value) => value.as_double().map(|value| Value::Float(value as f32)),
+            (TokenType::Double, value) => value.as_double().map(Value::Double),
+            (TokenType::Bool, value) => value.truth().map(Value::Bool),
+            _ => None,
+        };
+        converted.ok_or_else(|| {
+            let message = format!("Cannot assign {} to a variable of type '{:?}'", describe(&value.value_type()), variable_type);
+            ErrorMessage::new(&message, "Error", span)
+        })
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, ErrorMessage> {
+        match &expr.node {
+            ExprNode::IntLiteral(value) => Ok(Value::Int(*value)),
+            ExprNode::FloatLiteral(value) => Ok(Value::Float(*value)),
+            ExprNode::DoubleLiteral(value) => Ok(Value::Double(*value)),
+            ExprNode::CharLiteral(value) => Ok(Value::Char(*value)),
+            ExprNode::StringLiteral(value) => Ok(Value::String(value.clone())),
+            ExprNode::BoolLiteral(value) => Ok(Value::Bool(*value)),
+            ExprNode::Variable(name) => match self.variable(name) {
+                Some((_, value)) => Ok(value.clone()),
+                None => Err(ErrorMessage::new(&format!("Use of undeclared variable '{}'", name), "Error", expr.span)),
+            },
+            ExprNode::Index(..) => {
+                let (name, index) = self.element(expr)?;
+                let list = self.list(&name).expect("element() checked the list exists");
+                Ok(list.values[index].clone())
+            },
+            ExprNode::Unary(operator @ (TokenType::PlusPlus | TokenType::MinusMinus), target) => {
+                self.step(target, operator, expr.span).map(|(_, updated)| updated)
+            },
+            ExprNode::Postfix(target, operator) => {
+                self.step(target, operator, expr.span).map(|(original, _)| original)
+            },
+            ExprNode::Unary(operator, operand) => {
+                let value = self.evaluate(operand)?;
+                self.unary(operator, value, expr.span)
+            },
+            ExprNode::Binary(left, operator @ (TokenType::LogicalAnd | TokenType::LogicalOr), right) => {
+                // The right operand only runs when the left one does not decide the result
+                let left_value = self.condition(left)?;
+                if left_value == (*operator == TokenType::LogicalOr) {
+                    return Ok(Value::Bool(left_value));
+                }
+                Ok(Value::Bool(self.condition(right)?))
+            },
+            ExprNode::Binary(left, TokenType::Comma, right) => {
+                self.discard(left)?;
+                self.evaluate(right)
+            },
+            ExprNode::Binary(left, operator, right) => {
+                let left_value = self.evaluate(left)?;
+                let right_value = self.evaluate(right)?;
+                self.binary(operator, left_value, right_value, expr.span)
+            },
+            ExprNode::Ternary(condition, then_expr, else_expr) => {
+                if self.condition(condition)? {
+                    self.evaluate(then_expr)
+                } else {
+                    self.evaluate(else_expr)
+                }
+            },
+            ExprNode::Assign(target, operator, value) => {
+                let mut value = self.evaluate(value)?;
+                if let Some(operator) = compound_operator(operator) {
+                    let current = self.evaluate(target)?;
+                    value = self.binary(&operator, current, value, expr.span)?;
+                }
+                self.store(target, value)
+            },
+            ExprNode::Call(function, arguments) => self.call(function, arguments, expr.span)?.ok_or_else(|| {
+                let message = match self.functions.get(function).map(|function| &function.node) {
+                    Some(StmtNode::Function(TokenType::Void, ..)) => format!("Function '{}' does not return a value", function),
+                    _ => format!("Function '{}' ended without returning a value", function),
+                };
+                ErrorMessage::new(&message, "Error", expr.span)
+            }),
+        }
+    }
+
+    // Runs an expression only for its effects, so a call to a void function is
+    // allowed, as is one on either side of a ','.
+    fn discard(&mut self, expr: &Expr) -> Result<(), ErrorMessage> {
+        match &expr.node {
+            ExprNode::Call(function, arguments) => self.call(function, arguments, expr.span).map(|_| ()),
+            ExprNode::Binary(left, TokenType::Comma, right) => {
+                self.discard(left)?;
+                self.discard(right)
+            },
+            _ => self.evaluate(expr).map(|_| ()),
+        }
+    }
+
+    // Resolves `list[i][j]` to the list name and the checked row-major
+    // position of the element.
+    fn element(&mut self, target: &Expr) -> Result<(String, usize), ErrorMessage> {
+        let (list, indexes) = split_index(target);
+        let ExprNode::Variable(name) = &list.node else {
+            return Err(ErrorMessage::new("Only lists can be indexed", "Error", list.span));
+        };
+        let Some(dimensions) = self.list(name).map(|list| list.dimensions.clone()) else {
+            return Err(ErrorMessage::new(&format!("List '{}' not found", name), "Error", list.span));
+        };
+        if indexes.len() != dimensions.len() {
+            let message = format!("List '{}' expects {} index(es) but got {}", name, dimensions.len(), indexes.len());
+            return Err(ErrorMessage::new(&message, "Error", target.span));
+        }
+
+        let mut position = 0;
+        for (index, length) in indexes.into_iter().zip(dimensions) {
+            let Some(offset) = self.evaluate(index)?.as_int() else {
+                return Err(ErrorMessage::new("Expected an integer index", "Error", index.span));
+            };
+            match usize::try_from(offset) {
+                Ok(offset) if offset < length => position = position * length + offset,
+                _ => {
+                    let message = format!("Index {} is out of bounds for '{}' of size {}", offset, name, length);
+                    return Err(ErrorMessage::new(&message, "Error", index.span));
+                },
+            }
+        }
+        Ok((name.clone(), position))
+    }
+
+    fn store(&mut self, target: &Expr, value: Value) -> Result<Value, ErrorMessage> {
+        match &target.node {
+            ExprNode::Variable(name) => {
+                let Some((variable_type, _)) = self.variable(name).cloned() else {
+                    return Err(ErrorMessage::new(&format!("Use of undeclared variable '{}'", name), "Error", target.span));
+                };
+                let value = self.convert(value, &variable_type, target.span)?;
+                if let Some(scope) = self.lookup(name) {
+                    self.scopes[scope].variables.insert(name.clone(), (variable_type, value.clone()));
+                }
+                Ok(value)
+            },
+            ExprNode::Index(..) => {
+                let (name, index) = self.element(target)?;
+                let element_type = self.list(&name).map(|list| list.element_type.clone()).expect("element() checked the list exists");
+                let value = self.convert(value, &element_type, target.span)?;
+                if let Some(scope) = self.lookup(&name) {
+                    if let Some(list) = self.scopes[scope].lists.get_mut(&name) {
+                        list.values[index] = value.clone();
+                    }
+                }
+                Ok(value)
+            },
+            _ => Err(ErrorMessage::new("Left side of an assignment must be a variable", "Error", target.span)),
+        }
+    }
+
+    // Applies `++` or `--` to a variable, returning its value before and after.
+    fn step(&mut self, target: &Expr, operator: &TokenType, span: Span) -> Result<(Value, Value), ErrorMessage> {
+        let original = self.evaluate(target)?;
+        let arithmetic = if *operator == TokenType::PlusPlus { TokenType::Plus } else { TokenType::Minus };
+        let updated = self.binary(&arithmetic, original.clone(), Value::Int(1), span)?;
+        let updated = self.store(target, updated)?;
+        Ok((original, updated))
+    }
+
+    fn unary(&self, operator: &TokenType, value: Value, span: Span) -> Result<Value, ErrorMessage> {
+        let result = match (operator, &value) {
+            (TokenType::LogicalNot, value) => value.truth().map(|truth| Value::Bool(!truth)),
+            (TokenType::Minus, Value::Float(value)) => Some(Value::Float(-value)),
+            (TokenType::Minus, Value::Double(value)) => Some(Value::Double(-value)),
+            (TokenType::Minus, value) => value.as_int().map(|value| Value::Int(value.wrapping_neg())),
+            (TokenType::BitwiseNot, value) => value.as_int().map(|value| Value::Int(!value)),
+            _ => None,
+        };
+        result.ok_or_else(|| {
+            let message = format!("Operator '{}' cannot be applied to a value of type '{:?}'", operator_symbol(operator), value.value_type());
+            ErrorMessage::new(&message, "Error", span)
+        })
+    }
+
+    fn binary(&self, operator: &TokenType, left: Value, right: Value, span: Span) -> Result<Value, ErrorMessage> {
+        let mismatch = || {
+            let message = format!(
+                "Operator '{}' cannot be applied to values of type '{:?}' and '{:?}'",
+                operator_symbol(operator), left.value_type(), right.value_type(),
+            );
+            ErrorMessage::new(&message, "Error", span)
+        };
+        // Strings concatenate with strings and chars, and compare lexicographically
+        match (operator, &left, &right) {
+            (TokenType::Plus, Value::String(left_value), Value::String(right_value)) => {
+                return Ok(Value::String(format!("{}{}", left_value, right_value)));
+            },
+            (TokenType::Plus, Value::String(left_value), Value::Char(right_value)) => {
+                return Ok(Value::String(format!("{}{}", left_value, right_value)));
+            },
+            (TokenType::Plus, Value::Char(left_value), Value::String(right_value)) => {
+                return Ok(Value::String(format!("{}{}", left_value, right_value)));
+            },
+            (_, Value::String(left_value), Value::String(right_value)) => {
+                return compare(operator, Some(left_value.cmp(right_value))).ok_or_else(mismatch);
+            },
+            _ => (),
+        }
+        let Some(result_type) = arithmetic_type(&left.value_type(), &right.value_type()) else {
+            return Err(mismatch());
+        };
+
+        if result_type != TokenType::Int {
+            let (left_value, right_value) = (left.as_double().unwrap_or_default(), right.as_double().unwrap_or_default());
+            let result = match operator {
+                TokenType::Plus => left_value + right_value,
+                TokenType::Minus => left_value - right_value,
+                TokenType::Multiply => left_value * right_value,
+                TokenType::Divide => left_value / right_value,
+                _ => return compare(operator, left_value.partial_cmp(&right_value)).ok_or_else(mismatch),
+            };
+            return Ok(if result_type == TokenType::Float { Value::Float(result as f32) } else { Value::Double(result) });
+        }
+
+        let (left_value, right_value) = (left.as_int().unwrap_or_default(), right.as_int().unwrap_or_default());
+        let result = match operator {
+            TokenType::Plus => left_value.wrapping_add(right_value),
+            TokenType::Minus => left_value.wrapping_sub(right_value),
+            TokenType::Multiply => left_value.wrapping_mul(right_value),
+            TokenType::Divide | TokenType::Modulo if right_value == 0 => {
+                return Err(ErrorMessage::new("Division by zero", "Error", span));
+            },
+            TokenType::Divide => left_value.wrapping_div(right_value),
+            TokenType::Modulo => left_value.wrapping_rem(right_value),
+            TokenType::BitwiseAnd => left_value & right_value,
+            TokenType::BitwiseOr => left_value | right_value,
+            TokenType::BitwiseXor => left_value ^ right_value,
+            TokenType::ShiftLeft => left_value.wrapping_shl(right_value as u32),
+            TokenType::ShiftRight => left_value.wrapping_shr(right_value as u32),
+            _ => return compare(operator, Some(left_value.cmp(&right_value))).ok_or_else(mismatch),
+        };
+        Ok(Value::Int(result))
+    }
+}
+
+fn compare(operator: &TokenType, ordering: Option<std::cmp::Ordering>) -> Option<Value> {
+    let result = match operator {
+        TokenType::Equal => ordering.is_some_and(|ordering| ordering.is_eq()),
+        TokenType::NotEqual => !ordering.is_some_and(|ordering| ordering.is_eq()),
+        TokenType::LessThan => ordering.is_some_and(|ordering| ordering.is_lt()),
+        TokenType::LessThanOrEqual => ordering.is_some_and(|ordering| ordering.is_le()),
+        TokenType::GreaterThan => ordering.is_some_and(|ordering| ordering.is_gt()),
+        TokenType::GreaterThanOrEqual => ordering.is_some_and(|ordering| ordering.is_ge()),
+        _ => return None,
+    };
+    Some(Value::Bool(result))
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::token::{TokenType, TokenGlobal};
+
+// Language variants a course can select per request.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dialect {
+    // Strict C subset without the `string` and `bool` extensions
+    C,
+    #[default]
+    Teaching,
+}
+
+struct Keyword {
+    word: &'static str,
+    token_type: TokenType,
+    token_global: TokenGlobal,
+    teaching_only: bool,
+}
+
+const fn keyword(word: &'static str, token_type: TokenType, token_global: TokenGlobal, teaching_only: bool) -> Keyword {
+    Keyword { word, token_type, token_global, teaching_only }
+}
+
+// Every keyword the scanner knows about, matched against whole words only.
+const KEYWORDS: [Keyword; 20] = [
+    keyword("void", TokenType::Void, TokenGlobal::Identifier, false),
+    keyword("int", TokenType::Int, TokenGlobal::Identifier, false),
+    keyword("float", TokenType::Float, TokenGlobal::Identifier, false),
+    keyword("double", TokenType::Double, TokenGlobal::Identifier, false),
+    keyword("char", TokenType::Char, TokenGlobal::Identifier, false),
+    keyword("string", TokenType::String, TokenGlobal::Identifier, true),
+    keyword("bool", TokenType::Bool, TokenGlobal::Identifier, true),
+    keyword("true", TokenType::BooleanLiteral, TokenGlobal::Literal, true),
+    keyword("false", TokenType::BooleanLiteral, TokenGlobal::Literal, true),
+    keyword("for", TokenType::For, TokenGlobal::ReservedWord, false),
+    keyword("while", TokenType::While, TokenGlobal::ReservedWord, false),
+    keyword("return", TokenType::Return, TokenGlobal::ReservedWord, false),
+    keyword("if", TokenType::If, TokenGlobal::ReservedWord, false),
+    keyword("do", TokenType::Do, TokenGlobal::ReservedWord, false),
+    keyword("break", TokenType::Break, TokenGlobal::ReservedWord, false),
+    keyword("continue", TokenType::Continue, TokenGlobal::ReservedWord, false),
+    keyword("else", TokenType::Else, TokenGlobal::ReservedWord, false),
+    keyword("switch", TokenType::Switch, TokenGlobal::ReservedWord, false),
+    keyword("case", TokenType::Case, TokenGlobal::ReservedWord, false),
+    keyword("default", TokenType::Default, TokenGlobal::ReservedWord, false),
+];
+
+// Keyword selection for one scan: a dialect plus per-course adjustments on top of it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    pub dialect: Dialect,
+    pub enable_keywords: Vec<String>,
+    pub disable_keywords: Vec<String>,
+}
+
+impl LanguageConfig {
+    pub fn keyword_table(&self) -> HashMap<&'static str, (TokenType, TokenGlobal)> {
+        KEYWORDS.iter()
+            .filter(|keyword| {
+                let listed = |words: &Vec<String>| words.iter().any(|word| word == keyword.word);
+                let in_dialect = !keyword.teaching_only || self.dialect == Dialect::Teaching;
+                (in_dialect || listed(&self.enable_keywords)) && !listed(&self.disable_keywords)
+            })
+            .map(|keyword| (keyword.word, (keyword.token_type.clone(), keyword.token_global.clone())))
+            .collect()
+    }
+}
@@ -0,0 +1,12 @@
+pub mod checker;
+pub mod incremental;
+pub mod interpreter;
+pub mod keywords;
+pub mod parser;
+pub mod preprocessor;
+pub mod scanner;
+pub mod symbols;
+pub mod token;
+pub mod types;
+#[cfg(test)]
+mod tests;
@@ -1,9 +1,5 @@
-use warp::{Filter, Rejection, Reply};
-use serde::{Serialize, Deserialize};
-use parser::Parser;
-mod parser;
-mod scanner;
-mod token;
+use warp::Filter;
+use my_project::scanner;
 
 #[tokio::main]
 async fn main() {
@@ -1,122 +1,367 @@
-use std::collections::{HashMap, HashSet};
-use crate::token::{Token, TokenType, TokenGlobal};
-use serde::Serialize;
+use std::fmt;
+use crate::scanner::TokenStream;
+use crate::token::{LiteralValue, Span, Token, TokenType, TokenGlobal};
+use serde::{Deserialize, Serialize};
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExprNode {
-    Add(Box<ExprNode>, Box<ExprNode>),
-    LessThan(Box<ExprNode>, Box<ExprNode>),
-    Binary(Box<ExprNode>, TokenType, Box<ExprNode>),
     IntLiteral(i32),
     FloatLiteral(f32),
+    DoubleLiteral(f64),
     CharLiteral(char),
     StringLiteral(String),
     BoolLiteral(bool),
     Variable(String),
-    IntIdentifier(String),
-    FloatIdentifier(String),
-    BoolIdentifier(String),
-    StringIdentifier(String),
-    DoubleIdentifier(String),
-    CharIdentifier(String),
+    // list[index]
+    Index(Box<Expr>, Box<Expr>),
+    // Prefix operators, including `++` and `--`
+    Unary(TokenType, Box<Expr>),
+    // Postfix `++` and `--`
+    Postfix(Box<Expr>, TokenType),
+    Binary(Box<Expr>, TokenType, Box<Expr>),
+    // condition ? then : else
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    // Plain or compound assignment to the target on the left
+    Assign(Box<Expr>, TokenType, Box<Expr>),
+    // function(arguments)
+    Call(String, Vec<Expr>),
 }
 
-use std::fmt;
+// An expression along with the stretch of source it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub node: ExprNode,
+    pub span: Span,
+    // Filled in by the type checker
+    pub expr_type: Option<TokenType>,
+}
+
+impl Expr {
+    pub fn new(node: ExprNode, span: Span) -> Self {
+        Self { node, span, expr_type: None }
+    }
+}
 
 impl fmt::Display for ExprNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ExprNode::Add(left, right) => write!(f, "Add({}, {})", left, right),
-            ExprNode::LessThan(left, right) => write!(f, "LessThan({}, {})", left, right),
-            ExprNode::Binary(left, _, right) => write!(f, "Binary({}, {})", left, right),
             ExprNode::IntLiteral(value) => write!(f, "{}", value),
             ExprNode::FloatLiteral(value) => write!(f, "{}", value),
+            ExprNode::DoubleLiteral(value) => write!(f, "{}", value),
             ExprNode::CharLiteral(value) => write!(f, "{}", value),
             ExprNode::StringLiteral(value) => write!(f, "{}", value),
             ExprNode::BoolLiteral(value) => write!(f, "{}", value),
             ExprNode::Variable(name) => write!(f, "{}", name),
-            ExprNode::IntIdentifier(name) => write!(f, "{}", name),
-            ExprNode::FloatIdentifier(name) => write!(f, "{}", name),
-            ExprNode::BoolIdentifier(name) => write!(f, "{}", name),
-            ExprNode::StringIdentifier(name) => write!(f, "{}", name),
-            ExprNode::DoubleIdentifier(name) => write!(f, "{}", name),
-            ExprNode::CharIdentifier(name) => write!(f, "{}", name),
+            ExprNode::Index(list, index) => write!(f, "{}[{}]", list, index),
+            ExprNode::Unary(operator, operand) => write!(f, "{}{}", operator_symbol(operator), operand),
+            ExprNode::Postfix(operand, operator) => write!(f, "{}{}", operand, operator_symbol(operator)),
+            ExprNode::Binary(left, TokenType::Comma, right) => write!(f, "({}, {})", left, right),
+            ExprNode::Binary(left, operator, right) => write!(f, "({} {} {})", left, operator_symbol(operator), right),
+            ExprNode::Ternary(condition, then_expr, else_expr) => write!(f, "({} ? {} : {})", condition, then_expr, else_expr),
+            ExprNode::Assign(target, operator, value) => write!(f, "({} {} {})", target, operator_symbol(operator), value),
+            ExprNode::Call(function, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+                write!(f, "{}({})", function, arguments.join(", "))
+            },
         }
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+pub(crate) fn operator_symbol(operator: &TokenType) -> &'static str {
+    match operator {
+        TokenType::Plus => "+",
+        TokenType::Minus => "-",
+        TokenType::Multiply => "*",
+        TokenType::Divide => "/",
+        TokenType::Modulo => "%",
+        TokenType::PlusPlus => "++",
+        TokenType::MinusMinus => "--",
+        TokenType::Equal => "==",
+        TokenType::NotEqual => "!=",
+        TokenType::GreaterThan => ">",
+        TokenType::LessThan => "<",
+        TokenType::GreaterThanOrEqual => ">=",
+        TokenType::LessThanOrEqual => "<=",
+        TokenType::LogicalAnd => "&&",
+        TokenType::LogicalOr => "||",
+        TokenType::LogicalNot => "!",
+        TokenType::BitwiseAnd => "&",
+        TokenType::BitwiseOr => "|",
+        TokenType::BitwiseXor => "^",
+        TokenType::BitwiseNot => "~",
+        TokenType::ShiftLeft => "<<",
+        TokenType::ShiftRight => ">>",
+        TokenType::Assignment => "=",
+        TokenType::PlusAssignment => "+=",
+        TokenType::MinusAssignment => "-=",
+        TokenType::MultiplyAssignment => "*=",
+        TokenType::DivideAssignment => "/=",
+        TokenType::ModuloAssignment => "%=",
+        TokenType::BitwiseAndAssignment => "&=",
+        TokenType::BitwiseOrAssignment => "|=",
+        TokenType::BitwiseXorAssignment => "^=",
+        TokenType::ShiftLeftAssignment => "<<=",
+        TokenType::ShiftRightAssignment => ">>=",
+        _ => "?",
+    }
+}
+
+// The arithmetic operator behind a compound assignment such as `+=`.
+pub(crate) fn compound_operator(operator: &TokenType) -> Option<TokenType> {
+    let arithmetic = match operator {
+        TokenType::PlusAssignment => TokenType::Plus,
+        TokenType::MinusAssignment => TokenType::Minus,
+        TokenType::MultiplyAssignment => TokenType::Multiply,
+        TokenType::DivideAssignment => TokenType::Divide,
+        TokenType::ModuloAssignment => TokenType::Modulo,
+        TokenType::BitwiseAndAssignment => TokenType::BitwiseAnd,
+        TokenType::BitwiseOrAssignment => TokenType::BitwiseOr,
+        TokenType::BitwiseXorAssignment => TokenType::BitwiseXor,
+        TokenType::ShiftLeftAssignment => TokenType::ShiftLeft,
+        TokenType::ShiftRightAssignment => TokenType::ShiftRight,
+        _ => return None,
+    };
+    Some(arithmetic)
+}
+
+// Lexical and syntax errors reported before the parser gives up on the rest of
+// the program.
+const MAX_ERRORS: usize = 25;
+
+// Upper bound on the elements of one array, so a declaration cannot exhaust memory.
+const MAX_ARRAY_ELEMENTS: usize = 1 << 20;
+
+// How deeply the program may nest before the parser stops descending into it, so
+// deeply nested input is reported instead of overflowing the stack.
+pub(crate) const MAX_NESTING: usize = 256;
+
+const COMMA_PRECEDENCE: u8 = 0;
+const ASSIGNMENT_PRECEDENCE: u8 = 1;
+const CONDITIONAL_PRECEDENCE: u8 = 2;
+
+// The list an index expression such as `grid[i][j]` starts from, and its
+// indexes in source order.
+pub(crate) fn split_index(expr: &Expr) -> (&Expr, Vec<&Expr>) {
+    match &expr.node {
+        ExprNode::Index(list, index) => {
+            let (base, mut indexes) = split_index(list);
+            indexes.push(index);
+            (base, indexes)
+        },
+        _ => (expr, Vec::new()),
+    }
+}
+
+// Binding strength of each binary operator, following C: a higher number binds tighter.
+fn binary_precedence(operator: &TokenType) -> Option<u8> {
+    let precedence = match operator {
+        // `a, b` runs `a` for its effects and takes the value of `b`
+        TokenType::Comma => COMMA_PRECEDENCE,
+        TokenType::Assignment | TokenType::PlusAssignment | TokenType::MinusAssignment
+        | TokenType::MultiplyAssignment | TokenType::DivideAssignment | TokenType::ModuloAssignment
+        | TokenType::BitwiseAndAssignment | TokenType::BitwiseOrAssignment | TokenType::BitwiseXorAssignment
+        | TokenType::ShiftLeftAssignment | TokenType::ShiftRightAssignment => ASSIGNMENT_PRECEDENCE,
+        TokenType::QuestionMark => CONDITIONAL_PRECEDENCE,
+        TokenType::LogicalOr => 3,
+        TokenType::LogicalAnd => 4,
+        TokenType::BitwiseOr => 5,
+        TokenType::BitwiseXor => 6,
+        TokenType::BitwiseAnd => 7,
+        TokenType::Equal | TokenType::NotEqual => 8,
+        TokenType::LessThan | TokenType::LessThanOrEqual | TokenType::GreaterThan | TokenType::GreaterThanOrEqual => 9,
+        TokenType::ShiftLeft | TokenType::ShiftRight => 10,
+        TokenType::Plus | TokenType::Minus => 11,
+        TokenType::Multiply | TokenType::Divide | TokenType::Modulo => 12,
+        _ => return None,
+    };
+    Some(precedence)
+}
+
+fn is_variable_type(token_type: &TokenType) -> bool {
+    matches!(token_type, TokenType::Int | TokenType::Float | TokenType::Bool | TokenType::String | TokenType::Double | TokenType::Char)
+}
+
+// Keywords that begin a statement, where recovery after an error can resume.
+fn starts_statement(token_type: &TokenType) -> bool {
+    is_variable_type(token_type) || matches!(token_type,
+        TokenType::If | TokenType::While | TokenType::Do | TokenType::For | TokenType::Switch | TokenType::Case | TokenType::Default
+        | TokenType::Break | TokenType::Continue | TokenType::Return | TokenType::Void)
+}
+
+fn is_assignable(expr: &Expr) -> bool {
+    matches!(expr.node, ExprNode::Variable(_) | ExprNode::Index(..))
+}
+
+// The type C gives an integer literal: the first in int, long, long long, from
+// the width its suffix asks for, that holds the value. A 'u' suffix makes each
+// of them unsigned; hex and octal literals try the unsigned one after each.
+fn literal_type(value: u64, radix: u32, suffix: &str) -> Option<&'static str> {
+    let suffix = suffix.to_ascii_lowercase();
+    let unsigned = suffix.contains('u');
+    let widths = [("int", "unsigned int", i32::MAX as u64, u32::MAX as u64),
+        ("long", "unsigned long", i64::MAX as u64, u64::MAX),
+        ("long long", "unsigned long long", i64::MAX as u64, u64::MAX)];
+    widths.into_iter().skip(suffix.matches('l').count()).find_map(|(signed_name, unsigned_name, signed_max, unsigned_max)| {
+        if !unsigned && value <= signed_max {
+            Some(signed_name)
+        } else if (unsigned || radix != 10) && value <= unsigned_max {
+            Some(unsigned_name)
+        } else {
+            None
+        }
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum StmtNode {
-    Assignment(String, ExprNode),
-    ForLoop(Box<StmtNode>, Box<ExprNode>, Box<StmtNode>, Box<StmtNode>),
-    IfStatement(ExprNode, Box<StmtNode>, Option<Box<StmtNode>>),
-    WhileLoop(Box<ExprNode>, Box<StmtNode>),
-    DoWhileLoop(Box<ExprNode>, Box<StmtNode>),
-    SwitchCase(Box<ExprNode>, Vec<(ExprNode, StmtNode)>),
-    Block(Vec<StmtNode>),
+    // type name = value; where the initializer is optional
+    Declaration(TokenType, String, Option<Expr>),
+    // type name[rows][columns] = { values }; with the length of every
+    // dimension known and each value at its row-major position. Elements
+    // without a value are zero.
+    ListDeclaration(TokenType, String, Vec<usize>, Vec<(usize, Expr)>),
+    // type a = 1, b; declaring several names in the enclosing scope
+    Declarations(Vec<Stmt>),
+    Expression(Expr),
+    // for (init; condition; increment) body
+    ForLoop(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    IfStatement(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    WhileLoop(Expr, Box<Stmt>),
+    DoWhileLoop(Box<Stmt>, Expr),
+    // Each case label with the statements that follow it, in source order.
+    // The `default:` label is the one without a value.
+    SwitchCase(Expr, Vec<(Option<Expr>, Vec<Stmt>)>),
+    Block(Vec<Stmt>),
+    Break,
+    Continue,
+    Return(Option<Expr>),
+    // return_type name(parameters) body
+    Function(TokenType, String, Vec<(TokenType, String)>, Box<Stmt>),
+    // return_type name(parameter types); declaring a function defined elsewhere
+    Prototype(TokenType, String, Vec<TokenType>),
+    Empty,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub node: StmtNode,
+    pub span: Span,
 }
 
+impl Stmt {
+    pub fn new(node: StmtNode, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ProgramNode {
-    pub statements: Vec<StmtNode>,
+    pub statements: Vec<Stmt>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ErrorMessage {
-    message_type: String,
-    message: String,
-    line: usize,
-    column: usize,
+    pub message_type: String,
+    pub message: String,
+    #[serde(flatten)]
+    pub span: Span,
+}
+
+impl ErrorMessage {
+    pub fn new(message: &str, message_type: &str, span: Span) -> Self {
+        Self {
+            message_type: message_type.to_string(),
+            message: message.to_string(),
+            span,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+// The initialization, condition and increment of a for-loop header.
+type ForHeader = (Option<Stmt>, Option<Expr>, Option<Expr>);
+
+// Builds the syntax tree without evaluating anything; running the program is
+// left to the interpreter.
 pub struct Parser {
-    tokens: Vec<Token>,
-    declared_variables: HashMap<String, (TokenType, String)>,
-    lists: HashSet<String>,
-    process_list: HashMap<String, Vec<i32>>,
+    tokens: TokenStream,
     current: usize,
     errors: Vec<ErrorMessage>,
+    // Reported alongside the program, which still runs
+    warnings: Vec<ErrorMessage>,
+    // Return type and name of the function whose body is being parsed
+    function: Option<(TokenType, String)>,
+    // How many loops and switches enclose the statement being parsed, which
+    // decides where `break` and `continue` may appear
+    loops: usize,
+    switches: usize,
+    // How deep in the statement or expression being built the parser is, up to
+    // MAX_NESTING
+    depth: usize,
+    // Set once MAX_ERRORS is reached; the remaining input is then ignored
+    stopped: bool,
+    // Returned when looking past the last token
+    eof: Token,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: impl IntoIterator<Item = Token, IntoIter: 'static>) -> Self {
         Self {
-            tokens,
+            tokens: TokenStream::new(tokens.into_iter()),
             current: 0,
-            declared_variables: HashMap::new(),
             errors: Vec::new(),
-            lists: HashSet::new(),
-            process_list: HashMap::new(),
+            warnings: Vec::new(),
+            function: None,
+            loops: 0,
+            switches: 0,
+            depth: 0,
+            stopped: false,
+            eof: Token {
+                token_global: TokenGlobal::Symbol,
+                token_type: TokenType::Eof,
+                lexeme: String::new(),
+                span: Span::default(),
+                literal: None,
+                diagnostic: None,
+            },
         }
     }
 
     pub fn parse_program(&mut self) -> Result<ProgramNode, Vec<ErrorMessage>> {
-        // println!("current parse_prgrm: {:?}", self.current);
-
         let mut statements = Vec::new();
 
         while !self.is_at_end() {
-            match self.parse_statement() {
-                Ok(stmt) => {
-                    statements.push(stmt);
-                    println!("current parse_program: {:?}", self.current);
-                    self.current += 1;
-                    println!("current parse_program: {:?}", self.current);
-                },
+            let start = self.begin_statement();
+            let statement = if self.is_function_header() { self.parse_function() } else { self.parse_statement() };
+            match statement {
+                Ok(statement) => statements.push(statement),
                 Err(e) => {
-                    self.errors.push(e);
-
-                    if (self.current) < self.tokens.len() {
-                        let cur_line = self.tokens[self.current].line;
-                        while !self.is_at_end() && self.tokens[self.current].line == cur_line {
-                            self.current += 1;
-                        }
-                    }
+                    self.report(e);
+                    self.synchronize(start, false);
                 },
             }
         }
-        println!("errors total: {:?}", self.errors);
+        // Lexical errors are set aside as the tokens are pulled, ahead of the
+        // syntax errors; both are listed in source order and capped together
+        let mut errors = self.tokens.take_errors();
+        errors.append(&mut self.errors);
+        errors.sort_by_key(|error| error.span.start);
+        let last = errors.iter().enumerate()
+            .filter(|(_, error)| error.message_type == "Error")
+            .nth(MAX_ERRORS - 1)
+            .map(|(index, _)| index);
+        if let (true, Some(last)) = (self.stopped, last) {
+            errors.truncate(last + 1);
+            let span = errors[last].span;
+            errors.push(ErrorMessage::new("Too many errors; the rest of the program was not checked", "Error", span));
+        }
+        self.errors = errors;
 
         if self.errors.is_empty() {
             Ok(ProgramNode { statements })
@@ -125,983 +370,788 @@ impl Parser {
         }
     }
 
-    fn parse_statement(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let token = self.tokens[self.current].clone();
-        println!("current XXXXXXXX: {:?}", self.current);
-        match token.token_global {
-            TokenGlobal::Identifier => {
-                println!("entered identifier");
-                match token.token_type {
-                    TokenType::Int | TokenType::Float | TokenType::Bool | TokenType::String | TokenType::Double | TokenType::Char => self.parse_declaration(),
-                    _ => Err(self.error("Expected a type identifier", "Error")),
-                }
-            },
-            TokenGlobal::Variable => {
-                if self.tokens[self.current + 1].lexeme == "[" {
-                    let list_name = token.lexeme.clone();
-                    self.current += 2;
-                    let indx = self.tokens[self.current].lexeme.clone();
-                    self.current += 1;
-                    if self.tokens[self.current].lexeme == "]" {
-                        self.current += 1;
-                        if self.tokens[self.current].token_type == TokenType::Assignment {
-                            self.current += 1;
-                            let value = self.tokens[self.current].lexeme.clone();
-
-                            let index = match indx.parse::<usize>() {
-                                Ok(index) => index,
-                                Err(_) => return Err(self.error("Expected an integer index", "Error")),
-                            };
-                            println!("IS CONTAIN   {}", self.process_list.contains_key(&list_name));
-
-                            let list_values = match self.process_list.get_mut(&list_name) {
-                                Some(value) => value,
-                                None => {
-                                    // Handle the error here. For example, you can return an error or use a default value.
-                                    return Err(self.error(&format!("List '{}' not found", list_name), "Error"));
-                                }
-                            };
-                            if index >= list_values.len() {
-                                return Err(self.error("Index out of bounds", "Error"));
-                            }
-                            list_values[index] = match value.parse::<i32>() {
-                                Ok(value) => value,
-                                _ => return Err(self.error("Expected an integer value", "Error")),
-                            };
-                            self.current += 1;
-                            if self.match_token(TokenType::Semicolon).is_none() {
-                                return Err(self.error("Expected a semicolon", "Error"));
-                            }
-                            self.current -= 1;
-                        }
-                        return Ok(StmtNode::Block(vec![]));
-                    }
-                } else {
-
-                }
-                    self.parse_assignment()
-
-            },
-            TokenGlobal::ReservedWord => {
-                match token.token_type {
-                    TokenType::For => self.parse_for_statement(),
-                    TokenType::If => self.parse_if_statement(),
-                    TokenType::Do => self.parse_do_while_loop(),
-                    TokenType::Switch => self.parse_switch_case(),
-                    TokenType::While => self.parse_while_loop(),
-                    TokenType::OpenBrace => self.parse_block(0),
-                    TokenType::Case => {
-                        self.current += 1;
-                        Ok(StmtNode::Block(vec![]))
-                    }
-                    TokenType::Break => {
-                        self.current += 1;
-                        Ok(StmtNode::Block(vec![]))
-                    },
-                    _ => {
-                        println!("reserved word: {:?}", token.token_type);
-                        Err(self.error("Unexpected reserved word in statement", "Error"))
-                    },
-                }
-            },
-            TokenGlobal::Symbol => {
-                match token.token_type {
-                    TokenType::Semicolon => {
-                        self.current += 1;
-                        Ok(StmtNode::Block(vec![]))
-                    },
-                    TokenType::CloseBrace => {
-                        println!("current ZZZZZZZZZZ: {:?}", self.current);
-                        // self.current += 1;
-                        Ok(StmtNode::Block(vec![]))
-                    }
-                    _ => Err(self.error("Unexpected symbol in statement", "Error")),
-                }
-            },
-            TokenGlobal::List => {
-                let list_string = token.lexeme.clone();
-                if let Some(start) = list_string.find('{') {
-                    if let Some(end) = list_string.find('}') {
-                        let list_name = &list_string[..start].trim(); // Extract the list name
-                        let parts: Vec<&str> = list_name.split_whitespace().collect();
-                        let list_name = parts[1].trim_matches('[').trim_matches(']'); // Extract the list name without type and brackets
-                        let list_values_str = &list_string[start+1..end];
-                        let list_values: Vec<i32> = list_values_str.split(',')
-                            .map(|s| s.trim().parse().unwrap())
-                            .collect();
-                        println!("list_values: {:?}", list_values);
-                        println!("list_name: {:?}", list_name);
-                        self.process_list.insert(list_name.to_string(), list_values); // Insert the list name and values into the HashMap
-                    }
-                }
-                Ok(StmtNode::Block(vec![]))
-            },
-            _ => Err(self.error("Expected an identifier", "Error")),
-        }
+    // What parsing found suspicious without being wrong, such as a condition
+    // that is always true.
+    pub fn warnings(&self) -> &[ErrorMessage] {
+        &self.warnings
     }
 
-    fn parse_block(&mut self, flag_do_while: i32) -> Result<StmtNode, ErrorMessage> {
-        let mut statements = Vec::new();
-
-        if self.match_token(TokenType::OpenBrace).is_none() {
-            println!("PLEEEEEEEEESEEEEE NNNNNNNOOOOOOOO open");
-
-            self.errors.push(self.error("Expected '{'", "Error"));
-        }
-
+    fn parse_statement(&mut self) -> Result<Stmt, ErrorMessage> {
         if self.is_at_end() {
-            println!("a7a");
-            return Err(self.error("Expected '}'", "Error"));
-        }
-
-        while let Ok(stmt) = self.parse_statement() {
-
-            if (flag_do_while == 1 && self.tokens[self.current].lexeme == "while") {
-                break;
-            }
-
-            // println!("current Gaaaatttt: {:?}  {:?}", self.current, self.tokens[self.current]);
-            statements.push(stmt);
-            if self.match_token(TokenType::Semicolon).is_some() {
-                println!("HOOOOHHHHHHHH");
-                break;
-            }
-
-            if self.match_token(TokenType::CloseBrace).is_some() {
-                self.current -= 1;
-                break;
-            }
+            return Err(self.error("Expected a statement", "Error"));
         }
-
-        println!("current parse_block: {:?} {:?}", self.current, self.tokens[self.current]);
-        if self.match_token(TokenType::CloseBrace).is_none() {
-            println!("PLEEEEEEEEESEEEEE NNNNNNNOOOOOOOO close");
-            return Err(self.error("Expected '}'", "Error"));
+        let token = self.peek().clone();
+        match token.token_type {
+            _ if self.is_function_header() => Err(self.error("Functions can only be defined at the top level", "Error")),
+            token_type if is_variable_type(&token_type) => self.parse_declaration(),
+            TokenType::OpenBrace => self.parse_block(),
+            TokenType::If => self.parse_if_statement(),
+            TokenType::While => self.parse_while_loop(),
+            TokenType::Do => self.parse_do_while_loop(),
+            TokenType::For => self.parse_for_statement(),
+            TokenType::Switch => self.parse_switch_case(),
+            TokenType::Break | TokenType::Continue => self.parse_jump(),
+            TokenType::Return => self.parse_return(),
+            TokenType::Else => Err(self.error("'else' without a matching 'if'", "Error")),
+            TokenType::Case | TokenType::Default => Err(self.error(&format!("'{}' outside of a switch", token.lexeme), "Error")),
+            TokenType::Semicolon => {
+                self.current += 1;
+                Ok(Stmt::new(StmtNode::Empty, token.span))
+            },
+            _ => match token.token_global {
+                TokenGlobal::Identifier => Err(self.error("Expected a type identifier", "Error")),
+                TokenGlobal::ReservedWord => Err(self.error("Unexpected reserved word in statement", "Error")),
+                _ => self.parse_expression_statement(),
+            },
         }
-
-        Ok(StmtNode::Block(statements))
     }
 
-    fn parse_declaration(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let variable_type = self.tokens[self.current].token_type.clone();
-        println!("variable_type: {:?}", variable_type);
-
-        self.current += 1; // Consume the type identifier
-
-        let variable_token = self.tokens[self.current].clone();
-        if variable_token.token_global != TokenGlobal::Variable {
-            return Err(self.error("Expected a variable", "Error"));
-        }
-        let variable_name = variable_token.lexeme;
-        println!("variable_name: {:?}", variable_name);
-
-        if self.is_variable_declared(&variable_name) {
-            return Err(self.error(&format!("Variable '{}' already declared", variable_name), "Error"));
-        }
+    // A type (or `void`) followed by a name and '(' starts a function definition.
+    fn is_function_header(&mut self) -> bool {
+        let return_type = &self.peek().token_type;
+        (is_variable_type(return_type) || *return_type == TokenType::Void)
+            && self.peek_nth(1).token_global == TokenGlobal::Variable
+            && self.peek_nth(2).token_type == TokenType::OpenParen
+    }
 
-        self.current += 1; // Consume the variable
-        println!("current token: {:?}", self.tokens[self.current]);
-        if self.match_token(TokenType::Assignment).is_none() {
-            return Err(self.error("Expected an =", "Error"));
-        }
+    // A function definition, or a prototype when a ';' follows the parameters.
+    fn parse_function(&mut self) -> Result<Stmt, ErrorMessage> {
+        let return_type = self.advance();
+        let name = self.expect_variable()?;
+        let parameters = self.parse_header(Self::parse_parameters);
 
-        let expr = self.parse_expression()?;
-        let expr_type = self.get_expr_type(&expr)?;
-
-        if expr_type != variable_type {
-            match (expr_type, variable_type.clone()) {
-                (TokenType::Int, _) => return Err(self.error(&format!("Syntax Error: Cannot assign an Integer to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::Float, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a Float to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::Bool, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a Boolean to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::Char, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a Char to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::String, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a String to a variable of type '{:?}'", variable_type), "Error")),
-                _ => (),
-            }
-        }
-
-        if self.match_token(TokenType::Semicolon).is_none() {
-            return Err(self.error("Expected a semicolon", "Error"));
+        if self.match_token(TokenType::Semicolon).is_some() {
+            let span = return_type.span.to(self.previous_span());
+            let Some(parameters) = parameters else {
+                return Ok(Stmt::new(StmtNode::Empty, span));
+            };
+            let parameter_types = parameters.into_iter().map(|(parameter_type, _)| parameter_type.token_type).collect();
+            return Ok(Stmt::new(StmtNode::Prototype(return_type.token_type, name.lexeme, parameter_types), span));
         }
-        self.current -= 1;
-        println!("after declaration: {:?}", self.current);
-
-
-        self.declared_variables.insert(variable_name.clone(), (variable_type.clone(), expr.to_string()));
-        Ok(StmtNode::Assignment(variable_name, expr))
+        // Only a prototype may leave its parameters unnamed
+        let parameters = parameters.and_then(|parameters| {
+            parameters.into_iter()
+                .map(|(parameter_type, parameter)| match parameter {
+                    Some(parameter) => Ok((parameter_type.token_type, parameter)),
+                    None => Err(ErrorMessage::new("Expected a parameter name", "Error", parameter_type.span)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .inspect_err(|e| self.report(e.clone()))
+                .ok()
+        });
+
+        self.function = Some((return_type.token_type.clone(), name.lexeme.clone()));
+        let body = self.parse_block();
+        self.function = None;
+
+        let span = return_type.span.to(self.previous_span());
+        let (Some(parameters), body) = (parameters, body?) else {
+            return Ok(Stmt::new(StmtNode::Empty, span));
+        };
+        Ok(Stmt::new(StmtNode::Function(return_type.token_type, name.lexeme, parameters, Box::new(body)), span))
     }
 
-    fn parse_assignment(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let variable_name_before = self.tokens[self.current].lexeme.clone();
-
-        let variable_type = self.get_variable_type(&variable_name_before)?;
-
-        self.current += 1; // Consume the variable
-
-        let operator = self.tokens[self.current].token_type.clone();
-        println!("{:?} enta meeeen:   ", operator);
-        self.current += 1; // Consume the operator
-        println!("current parse_assignment: {:?}", self.tokens[self.current]);
-
-        let right = match operator {
-            TokenType::Plus => {
-                if self.tokens[self.current].token_type == TokenType::Plus {
-                    self.current += 1; // Consume the second '+'
-                    let updated_value = match self.declared_variables.get(&variable_name_before) {
-                        Some(value) => match value.1.parse::<i32>() {
-                            Ok(parsed_value) => parsed_value + 1,
-                            Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                        },
-                        None => return Err(self.error("Variable not found", "Error")),
-                    };
-                    self.update_variable_value(variable_name_before.clone(), updated_value.to_string())?;
-                    ExprNode::IntLiteral(1) // Handle i++
-                } else if self.tokens[self.current].token_type == TokenType::Assignment {
-                    self.current += 1; // Consume the '='
-                    let increment_value = match self.tokens[self.current].lexeme.parse::<i32>() {
-                        Ok(value) => value,
-                        Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                    };
-                    let updated_value = match self.declared_variables.get(&variable_name_before) {
-                        Some(value) => match value.1.parse::<i32>() {
-                            Ok(parsed_value) => parsed_value + increment_value,
-                            Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                        },
-                        None => return Err(self.error("Variable not found", "Error")),
-                    };
-                    self.update_variable_value(variable_name_before.clone(), updated_value.to_string())?;
-                    self.parse_expression()? // Handle i += 1
-                } else {
-                    return Err(self.error("Expected '+' or '=' after '+'", "Error"));
+    // The parameter types with their names, which a prototype may leave out.
+    fn parse_parameters(&mut self) -> Result<Vec<(Token, Option<String>)>, ErrorMessage> {
+        self.expect(TokenType::OpenParen, "Expected '('")?;
+        let mut parameters: Vec<(Token, Option<String>)> = Vec::new();
+        // `f()` and `f(void)` both take no parameters
+        if !(self.check(TokenType::Void) && self.peek_nth(1).token_type == TokenType::CloseParen) {
+            while !self.check(TokenType::CloseParen) {
+                if !is_variable_type(&self.peek().token_type) {
+                    return Err(self.error("Expected a parameter type", "Error"));
                 }
-            },
-            TokenType::Minus => {
-                if self.tokens[self.current].token_type == TokenType::Minus {
-                    self.current += 1; // Consume the second '+'
-                    let updated_value = match self.declared_variables.get(&variable_name_before) {
-                        Some(value) => match value.1.parse::<i32>() {
-                            Ok(parsed_value) => parsed_value - 1,
-                            Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                        },
-                        None => return Err(self.error("Variable not found", "Error")),
-                    };
-                    self.update_variable_value(variable_name_before.clone(), updated_value.to_string())?;
-                    ExprNode::IntLiteral(1) // Handle i++
-                } else if self.tokens[self.current].token_type == TokenType::Assignment {
-                    self.current += 1; // Consume the '='
-                    let increment_value = match self.tokens[self.current].lexeme.parse::<i32>() {
-                        Ok(value) => value,
-                        Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                    };
-                    let updated_value = match self.declared_variables.get(&variable_name_before) {
-                        Some(value) => match value.1.parse::<i32>() {
-                            Ok(parsed_value) => parsed_value - increment_value,
-                            Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                        },
-                        None => return Err(self.error("Variable not found", "Error")),
-                    };
-                    self.update_variable_value(variable_name_before.clone(), updated_value.to_string())?;
-                    self.parse_expression()? // Handle i += 1
+                let parameter_type = self.advance();
+                if self.peek().token_global != TokenGlobal::Variable {
+                    parameters.push((parameter_type, None));
                 } else {
-                    return Err(self.error("Expected '+' or '=' after '+'", "Error"));
-                }
-            },
-            TokenType::Assignment => { // x = x + 1
-                if self.tokens[self.current].token_type == TokenType::Variable
-                    && self.is_variable_declared(&self.tokens[self.current].lexeme)
-                    && self.tokens[self.current + 1].token_type == TokenType::Plus {
-                    let variable_name = self.tokens[self.current].lexeme.clone();
-                    self.current += 2; // Consume the variable and '+'
-                    if  TokenType::IntegerLiteral == self.tokens[self.current].token_type
-                        || TokenType::Variable == self.tokens[self.current].token_type {
-
-                        let increment_value = match self.tokens[self.current].token_type {
-                            TokenType::IntegerLiteral => self.tokens[self.current].lexeme.parse::<i32>().unwrap_or_default(),
-                            TokenType::Variable => {
-                                let variable_name = &self.tokens[self.current].lexeme;
-                                match self.declared_variables.get(variable_name) {
-                                    Some((_, value)) => value.parse::<i32>().unwrap_or_default(),
-                                    None => return Err(self.error("Variable not found", "Error")),
-                                }
-                            },
-                            _ => return Err(self.error("Expected a valid integer or variable", "Error")),
-                        };
-
-                        println!("Before {}", self.declared_variables.get(&variable_name).unwrap().1);
-                        let variable_value = match self.declared_variables.get(&variable_name) {
-                            Some(value) => match value.1.parse::<i32>() {
-                                Ok(parsed_value) => parsed_value,
-                                Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                            },
-                            None => return Err(self.error("Variable not found", "Error")),
-                        };
-                        let updated_value = variable_value + increment_value;
-                        self.update_variable_value(variable_name_before.clone(), updated_value.to_string())?;
-                        println!("After {}", self.declared_variables.get(&variable_name).unwrap().1);
-                        self.current += 1;
-                        ExprNode::IntLiteral(updated_value) // Handle i = i + n
-                    } else {
-                        return Err(self.error("Expected an integer after '+'", "Error"));
+                    let parameter = self.advance();
+                    if parameters.iter().any(|(_, existing)| existing.as_ref() == Some(&parameter.lexeme)) {
+                        let message = format!("Parameter '{}' already declared", parameter.lexeme);
+                        return Err(ErrorMessage::new(&message, "Error", parameter.span));
                     }
-
-
+                    parameters.push((parameter_type, Some(parameter.lexeme)));
                 }
-                else if self.tokens[self.current].token_type == TokenType::Variable
-                    && self.is_variable_declared(&self.tokens[self.current].lexeme)
-                        && self.tokens[self.current + 1].token_type == TokenType::Minus {
-                    let variable_name = self.tokens[self.current].lexeme.clone();
-                    self.current += 2; // Consume the variable and '+'
-                    if  TokenType::IntegerLiteral == self.tokens[self.current].token_type
-                        || TokenType::Variable == self.tokens[self.current].token_type {
-
-                        let increment_value = match self.tokens[self.current].token_type {
-                            TokenType::IntegerLiteral => self.tokens[self.current].lexeme.parse::<i32>().unwrap_or_default(),
-                            TokenType::Variable => {
-                                let variable_name = &self.tokens[self.current].lexeme;
-                                match self.declared_variables.get(variable_name) {
-                                    Some((_, value)) => value.parse::<i32>().unwrap_or_default(),
-                                    None => return Err(self.error("Variable not found", "Error")),
-                                }
-                            },
-                            _ => return Err(self.error("Expected a valid integer or variable", "Error")),
-                        };
-
-                        println!("Before {}", self.declared_variables.get(&variable_name).unwrap().1);
-                        let variable_value = match self.declared_variables.get(&variable_name) {
-                            Some(value) => match value.1.parse::<i32>() {
-                                Ok(parsed_value) => parsed_value,
-                                Err(_) => return Err(self.error("Expected a valid integer", "Error")),
-                            },
-                            None => return Err(self.error("Variable not found", "Error")),
-                        };
-                        let updated_value = variable_value - increment_value;
-                        self.update_variable_value(variable_name_before.clone(), updated_value.to_string())?;
-                        println!("After {}", self.declared_variables.get(&variable_name).unwrap().1);
-                        self.current += 1;
-                        ExprNode::IntLiteral(updated_value) // Handle i = i + n
-                    } else {
-                        return Err(self.error("Expected an integer after '+'", "Error"));
-                    }
-
-
-                } else {
-                    self.parse_expression()? // Handle i = expression
+                if self.match_token(TokenType::Comma).is_none() {
+                    break;
                 }
-            },
-            _ => return Err(self.error("Expected an assignment operator", "Error")),
-        };
-
-
-        // println!("current AFTER: {:?}", self.tokens[self.current]);
-
-        let right_type = self.get_expr_type(&right)?;
-        if right_type != variable_type && self.is_valid_variable_type(&variable_type){
-            match (right_type, variable_type.clone()) {
-                (TokenType::Int, _) => return Err(self.error(&format!("Syntax Error: Cannot assign an Integer to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::Float, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a Float to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::Bool, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a Boolean to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::Char, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a Char to a variable of type '{:?}'", variable_type), "Error")),
-                (TokenType::String, _) => return Err(self.error(&format!("Syntax Error: Cannot assign a String to a variable of type '{:?}'", variable_type), "Error")),
-                _ => (),
             }
+        } else {
+            self.current += 1; // Consume 'void'
         }
+        self.expect(TokenType::CloseParen, "Expected ')'")?;
+        Ok(parameters)
+    }
 
-        let expr = ExprNode::Binary(Box::new(ExprNode::Variable(variable_name_before.clone())), operator, Box::new(right));
-        if self.match_token(TokenType::Semicolon).is_none() {
-            self.errors.push(self.error("Expected a semicolon", "Error"));
-        }
-        self.current -= 1;
-
-        Ok(StmtNode::Assignment(variable_name_before, expr))
+    fn parse_block(&mut self) -> Result<Stmt, ErrorMessage> {
+        self.nested(Self::parse_block_statements)
     }
 
-    fn parse_expression(&mut self) -> Result<ExprNode, ErrorMessage> {
-        let mut expr = self.parse_term()?;
+    fn parse_block_statements(&mut self) -> Result<Stmt, ErrorMessage> {
+        let open = self.expect(TokenType::OpenBrace, "Expected '{'")?;
 
-        while let Some(operator) = self.match_token(TokenType::Plus).or(self.match_token(TokenType::Minus)) {
-            let right = self.parse_term()?;
-            expr = ExprNode::Binary(Box::new(expr), operator, Box::new(right));
+        let mut statements = Vec::new();
+        while !self.check(TokenType::CloseBrace) {
+            if self.is_at_end() {
+                return Err(self.error("Expected '}'", "Error"));
+            }
+            let start = self.begin_statement();
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.report(e);
+                    self.synchronize(start, true);
+                },
+            }
         }
+        self.current += 1; // Consume the '}'
 
-        Ok(expr)
+        Ok(Stmt::new(StmtNode::Block(statements), open.span.to(self.previous_span())))
     }
 
-    fn parse_term(&mut self) -> Result<ExprNode, ErrorMessage> {
-        let mut expr = self.parse_factor()?;
-
-        while let Some(operator) = self.match_token(TokenType::Multiply).or(self.match_token(TokenType::Divide)) {
-            let right = self.parse_factor()?;
-            expr = ExprNode::Binary(Box::new(expr), operator, Box::new(right));
+    // The body of an `if`, `else` or loop: a block or a single statement. A
+    // declaration on its own is not a statement in C, as nothing could use it.
+    fn parse_body(&mut self, keyword: &str) -> Result<Stmt, ErrorMessage> {
+        if is_variable_type(&self.peek().token_type) && !self.is_function_header() {
+            let message = format!("A declaration cannot be the body of '{}' without braces", keyword);
+            return Err(self.error(&message, "Error"));
         }
+        self.nested(Self::parse_statement)
+    }
 
-        Ok(expr)
+    // A loop body, inside which `break` and `continue` refer to the loop.
+    fn parse_loop_body(&mut self, keyword: &str) -> Result<Stmt, ErrorMessage> {
+        self.loops += 1;
+        let body = self.parse_body(keyword);
+        self.loops -= 1;
+        body
     }
 
-    fn parse_factor(&mut self) -> Result<ExprNode, ErrorMessage> {
-        let token = self.tokens[self.current].clone();
+    fn parse_declaration(&mut self) -> Result<Stmt, ErrorMessage> {
+        let declaration = self.parse_declaration_without_semicolon()?;
+        self.expect(TokenType::Semicolon, "Expected a semicolon")?;
+        Ok(Stmt::new(declaration.node, declaration.span.to(self.previous_span())))
+    }
 
-        println!("toXXXXXXXXXken: {:?}", token);
-        match &token.token_type {
-            TokenType::OpenParen => {
-                self.current += 1; // Consume the OpenParen token
-                let expr = self.parse_expression()?;
-                if let TokenType::CloseParen = self.tokens[self.current].token_type {
-                    self.current += 1; // Consume the CloseParen token
-                    Ok(expr)
-                } else {
-                    Err(self.error("Expected a closing parenthesis", "Error"))
-                }
-            },
-            TokenType::IntegerLiteral => {
-                match token.lexeme.parse::<i32>() {
-                    Ok(value) => {
-                        println!("FUCKED UPPPPP %%%%%%%%!!!!!!!!!!");
-                        self.current += 1; // Consume the literal token
-                        Ok(ExprNode::IntLiteral(value))
-                    },
-                    Err(_) => Err(self.error("Expected a valid integer", "Error")),
-                }
-            },
-            TokenType::FloatingLiteral => {
-                match token.lexeme.parse::<f32>() {
-                    Ok(value) => {
-                        self.current += 1; // Consume the literal token
-                        Ok(ExprNode::FloatLiteral(value))
-                    },
-                    Err(_) => Err(self.error("Expected a valid float", "Error")),
-                }
-            },
-            TokenType::CharacterLiteral => {
-                if token.lexeme.len() == 1 {
-                    self.current += 1; // Consume the literal token
-                    Ok(ExprNode::CharLiteral(token.lexeme.chars().next().unwrap()))
-                } else {
-                    Err(self.error("Expected a valid character", "Error"))
-                }
-            },
-            TokenType::StringLiteral => {
-                self.current += 1; // Consume the literal token
+    fn parse_declaration_without_semicolon(&mut self) -> Result<Stmt, ErrorMessage> {
+        if !is_variable_type(&self.peek().token_type) {
+            return Err(self.error("Expected a type identifier", "Error"));
+        }
+        let type_token = self.advance();
 
-                Ok(ExprNode::StringLiteral(token.lexeme.clone()))
-            },
-            TokenType::BooleanLiteral => {
-                match token.lexeme.parse::<bool>() {
-                    Ok(value) => {
-                        self.current += 1; // Consume the literal token
-                        Ok(ExprNode::BoolLiteral(value))
-                    },
-                    Err(_) => Err(self.error("Expected a valid boolean", "Error")),
-                }
-            },
-            TokenType::Variable => {
-                if !self.is_variable_declared(&token.lexeme) {
-                    self.errors.push(self.error(&format!("Use of undeclared variable '{}'", token.lexeme), "Error"));                }
-                self.current += 1; // Consume the variable token
-                Ok(ExprNode::Variable(token.lexeme.clone()))
-            },
-            _ => Err(self.error("Expected a number, variable, or expression", "Error")),
+        let mut declarations = vec![self.parse_declarator(&type_token.token_type, type_token.span)?];
+        while self.match_token(TokenType::Comma).is_some() {
+            let start = self.peek().span;
+            declarations.push(self.parse_declarator(&type_token.token_type, start)?);
+        }
+
+        if declarations.len() == 1 {
+            return Ok(declarations.remove(0));
         }
+        Ok(Stmt::new(StmtNode::Declarations(declarations), type_token.span.to(self.previous_span())))
     }
 
-    fn parse_comparison(&mut self) -> Result<ExprNode, ErrorMessage> {
-        let left = self.parse_expression()?;
-
-        let operator = match &self.tokens[self.current].token_type {
-            TokenType::Equal => TokenType::Equal,
-            TokenType::NotEqual => TokenType::NotEqual,
-            TokenType::LessThan => TokenType::LessThan,
-            TokenType::LessThanOrEqual => TokenType::LessThanOrEqual,
-            TokenType::GreaterThan => TokenType::GreaterThan,
-            TokenType::GreaterThanOrEqual => TokenType::GreaterThanOrEqual,
-            _ => return Err(self.error("Expected a comparison operator", "Error")),
+    // One name of a declaration, with its optional initializer.
+    fn parse_declarator(&mut self, variable_type: &TokenType, start: Span) -> Result<Stmt, ErrorMessage> {
+        let variable_token = self.expect_variable()?;
+        if self.check(TokenType::OpenBracket) {
+            return self.parse_list_declaration(variable_type, variable_token, start);
+        }
+        let value = if self.match_token(TokenType::Assignment).is_some() {
+            Some(self.parse_precedence(ASSIGNMENT_PRECEDENCE)?)
+        } else {
+            None
         };
 
-        self.current += 1; // Consume the operator
+        let node = StmtNode::Declaration(variable_type.clone(), variable_token.lexeme, value);
+        Ok(Stmt::new(node, start.to(self.previous_span())))
+    }
 
-        let right = self.parse_expression()?;
+    fn parse_list_declaration(&mut self, element_type: &TokenType, list_token: Token, start: Span) -> Result<Stmt, ErrorMessage> {
+        let mut dimensions = Vec::new();
+        while self.match_token(TokenType::OpenBracket).is_some() {
+            let token = self.peek().clone();
+            let size = match token.token_type {
+                TokenType::IntegerLiteral => {
+                    let size = self.int_literal_value(&token)?;
+                    self.current += 1;
+                    if size == 0 {
+                        let message = format!("Array '{}' must have a positive size", list_token.lexeme);
+                        return Err(ErrorMessage::new(&message, "Error", token.span));
+                    }
+                    usize::try_from(size).ok()
+                },
+                _ if !dimensions.is_empty() => {
+                    let message = format!("Only the first dimension of '{}' may be omitted", list_token.lexeme);
+                    return Err(self.error(&message, "Error"));
+                },
+                _ => None,
+            };
+            self.expect(TokenType::CloseBracket, "Expected ']'")?;
+            dimensions.push(size);
+        }
 
-        // Evaluate the condition if left and right are variables
-        let left_value = match &left {
-            ExprNode::Variable(name) => self.declared_variables.get(name).map(|(_, value)| value.parse::<i32>().unwrap_or_default()),
-            _ => None,
-        };
-        let right_value = match &right {
-            ExprNode::Variable(name) => self.declared_variables.get(name).map(|(_, value)| value.parse::<i32>().unwrap_or_default()),
-            ExprNode::IntLiteral(value) => Some(*value),
-            _ => None,
+        // The number of elements in one entry of the first dimension
+        let too_large = || ErrorMessage::new(&format!("Array '{}' is too large", list_token.lexeme), "Error", list_token.span);
+        let stride = dimensions[1..].iter()
+            .try_fold(1usize, |stride, size| stride.checked_mul(size.unwrap_or_default()))
+            .ok_or_else(too_large)?;
+
+        let mut values = Vec::new();
+        let covered = if self.match_token(TokenType::Assignment).is_none() {
+            if dimensions[0].is_none() {
+                // Without an initializer there is nothing to count the elements from
+                let message = format!("Array size missing in '{}'", list_token.lexeme);
+                return Err(ErrorMessage::new(&message, "Error", list_token.span));
+            }
+            0
+        } else if self.check(TokenType::StringLiteral) && dimensions.len() == 1 {
+            self.parse_string_initializer(element_type, dimensions[0], 0, &mut values)?
+        } else {
+            let open = self.current;
+            self.parse_initializer_list(element_type, &dimensions, 0, &mut values).inspect_err(|_| {
+                // Recovery then carries on after the whole initializer
+                self.current = open;
+                self.skip_braces();
+            })?
         };
 
-        if let (Some(left_value), Some(right_value)) = (left_value, right_value) {
-            let result = match operator {
-                TokenType::Equal => left_value == right_value,
-                TokenType::NotEqual => left_value != right_value,
-                TokenType::LessThan => left_value < right_value,
-                TokenType::LessThanOrEqual => left_value <= right_value,
-                TokenType::GreaterThan => left_value > right_value,
-                TokenType::GreaterThanOrEqual => left_value >= right_value,
-                _ => return Err(self.error("Invalid comparison operator", "Error")),
-            };
-            return Ok(ExprNode::BoolLiteral(result));
+        // An omitted first dimension is as long as its initializer needs
+        let first = dimensions[0].unwrap_or(covered.div_ceil(stride));
+        if first == 0 {
+            let message = format!("Array '{}' must have a positive size", list_token.lexeme);
+            return Err(ErrorMessage::new(&message, "Error", list_token.span));
         }
+        if first.checked_mul(stride).is_none_or(|total| total > MAX_ARRAY_ELEMENTS) {
+            return Err(too_large());
+        }
+        let dimensions = std::iter::once(first).chain(dimensions[1..].iter().flatten().copied()).collect();
 
-        Ok(ExprNode::Binary(Box::new(left), operator, Box::new(right)))
+        let node = StmtNode::ListDeclaration(element_type.clone(), list_token.lexeme, dimensions, values);
+        Ok(Stmt::new(node, start.to(self.previous_span())))
     }
 
-    fn parse_condition(&mut self) -> Result<ExprNode, ErrorMessage> {
-        let current = self.current;
-        match self.parse_comparison() {
-            Ok(comparison) => {
-                if let ExprNode::Binary(left, operator, right) = &comparison {
-                    let left_value = match &**left {
-                        ExprNode::Variable(name) => self.declared_variables.get(name).map(|(_, value)| value.parse::<i32>().unwrap_or_default()),
-                        ExprNode::IntLiteral(value) => Some(*value),
-                        _ => None,
-                    };
-                    let right_value = match &**right {
-                        ExprNode::Variable(name) => self.declared_variables.get(name).map(|(_, value)| value.parse::<i32>().unwrap_or_default()),
-                        ExprNode::IntLiteral(value) => Some(*value),
-                        _ => None,
-                    };
-
-
-                    if let (Some(left_value), Some(right_value)) = (left_value, right_value) {
-                        let result = match operator {
-                            TokenType::GreaterThan => left_value > right_value,
-                            TokenType::LessThan => left_value < right_value,
-                            TokenType::Equal => left_value == right_value,
-                            TokenType::NotEqual => left_value != right_value,
-                            TokenType::GreaterThanOrEqual => left_value >= right_value,
-                            TokenType::LessThanOrEqual => left_value <= right_value,
-                            _ => return Err(self.error("Invalid comparison operator", "Error")),
-                        };
-                        return Ok(ExprNode::BoolLiteral(result));
-                    }
-                }
-                Ok(comparison)
-            },
-            Err(_) => {
-                self.current = current;
-                let expr = self.parse_expression()?;
-                match &expr {
-                    ExprNode::BoolLiteral(value) => {
-                        if *value {
-                            self.errors.push(self.error("Warning: This condition is always true", "Warning"));
-                        }
-                    },
-                    ExprNode::IntLiteral(value) => {
-                        if *value != 0 {
-                            self.errors.push(self.error("Warning: This condition is always true", "Warning"));
-                        } else {
-                            self.errors.push(self.error("Warning: This condition is always false", "Warning"));
-                        }
-                    },
-                    _ => (),
-                }
-                Ok(expr)
-            },
+    // A brace-enclosed initializer for an array with `dimensions`, whose first
+    // one may still be unknown. Nested braces fill one entry of the first
+    // dimension each; plain values fill the elements in order, as C allows
+    // braces to be left out. Each value goes into `values` at its row-major
+    // position after `base`, and the number of positions covered is returned.
+    fn parse_initializer_list(
+        &mut self,
+        element_type: &TokenType,
+        dimensions: &[Option<usize>],
+        base: usize,
+        values: &mut Vec<(usize, Expr)>,
+    ) -> Result<usize, ErrorMessage> {
+        let stride: usize = dimensions[1..].iter().flatten().product();
+        let capacity = dimensions[0].map(|size| size.saturating_mul(stride));
+
+        self.expect(TokenType::OpenBrace, "Expected '{'")?;
+        let mut cursor: usize = 0;
+        while !self.check(TokenType::CloseBrace) {
+            let is_row = dimensions.len() == 2 && self.check(TokenType::StringLiteral);
+            let is_nested = dimensions.len() > 1 && (self.check(TokenType::OpenBrace) || is_row);
+            if !is_nested && self.check(TokenType::OpenBrace) {
+                return Err(self.error("Expected a value instead of '{'", "Error"));
+            }
+            if is_nested {
+                cursor = cursor.next_multiple_of(stride);
+            }
+            if capacity.is_some_and(|capacity| cursor >= capacity) {
+                return Err(self.error("Too many values in list initialization", "Error"));
+            }
+
+            if is_row {
+                self.parse_string_initializer(element_type, dimensions[1], base + cursor, values)?;
+                cursor += stride;
+            } else if is_nested {
+                self.nested(|parser| parser.parse_initializer_list(element_type, &dimensions[1..], base + cursor, values))?;
+                cursor += stride;
+            } else {
+                values.push((base + cursor, self.parse_precedence(ASSIGNMENT_PRECEDENCE)?));
+                cursor += 1;
+            }
+            if self.match_token(TokenType::Comma).is_none() {
+                break;
+            }
         }
+        self.expect(TokenType::CloseBrace, "Expected '}'")?;
+        Ok(cursor)
     }
 
-    fn parse_if_statement(&mut self) -> Result<StmtNode, ErrorMessage> {
-        if self.match_token(TokenType::If).is_none() {
-            self.errors.push(self.error("Expected 'if'", "Error"));
+    // The characters of a string initializer for a char array of `size`,
+    // stored from `base`. Returns how many characters were stored.
+    fn parse_string_initializer(
+        &mut self,
+        element_type: &TokenType,
+        size: Option<usize>,
+        base: usize,
+        values: &mut Vec<(usize, Expr)>,
+    ) -> Result<usize, ErrorMessage> {
+        let string = self.advance();
+        if *element_type != TokenType::Char {
+            return Err(ErrorMessage::new("Only char arrays can be initialized from a string", "Error", string.span));
         }
-
-        if self.match_token(TokenType::OpenParen).is_none() {
-            self.errors.push(self.error("Expected '('", "Error"));
+        let Some(LiteralValue::String(text)) = &string.literal else {
+            return Err(ErrorMessage::new("Expected a valid string", "Error", string.span));
+        };
+        let length = text.chars().count();
+        if size.is_some_and(|size| length > size) {
+            return Err(ErrorMessage::new("Too many values in list initialization", "Error", string.span));
         }
+        // The terminating '\0' is only kept when the array has room for it
+        let characters = text.chars().chain(std::iter::once('\0')).take(size.unwrap_or(length + 1));
+        let start = values.len();
+        values.extend(characters.enumerate().map(|(position, character)| {
+            (base + position, Expr::new(ExprNode::CharLiteral(character), string.span))
+        }));
+        Ok(values.len() - start)
+    }
 
-        let condition = match self.parse_condition() {
-            Ok(condition) => condition,
-            Err(err) if err.message_type == "Warning" => {
-                println!("Warning: {:?}", err.message);
-                self.errors.push(err);
-                ExprNode::BoolLiteral(false) // Use a default value
-            },
-            Err(err) => return Err(err),
-        };
+    fn parse_expression_statement(&mut self) -> Result<Stmt, ErrorMessage> {
+        let expr = self.parse_expression()?;
+        self.expect(TokenType::Semicolon, "Expected a semicolon")?;
+        let span = expr.span.to(self.previous_span());
+        Ok(Stmt::new(StmtNode::Expression(expr), span))
+    }
 
-        if self.match_token(TokenType::CloseParen).is_none() {
-            self.errors.push(self.error("Expected ')'", "Error"));
-        }
+    fn parse_if_statement(&mut self) -> Result<Stmt, ErrorMessage> {
+        let start = self.expect(TokenType::If, "Expected 'if'")?.span;
+        let condition = self.parse_header(Self::parse_parenthesized_condition);
 
-        let then_branch = self.parse_block(0)?;
-        // self.current += 1;
-        // println!("current parse_if_statement: {:?}, {:?}", self.current, self.tokens[self.current]);
+        let then_branch = self.parse_body("if")?;
+        // An `else` belongs to the nearest `if` without one, so in
+        // `if (a) if (b) x; else y;` it is taken by the inner `if` here.
+        // `else if` needs nothing special: the `if` is the else branch.
         let else_branch = if self.match_token(TokenType::Else).is_some() {
-            if self.match_token(TokenType::If).is_some() {
-                // Handle 'else if'
-                self.current -= 1; // Go back to the 'if' token
-                Some(self.parse_if_statement()?)
-            } else {
-                // Handle 'else'
-                Some(self.parse_block(0)?)
-            }
+            Some(self.parse_body("else")?)
         } else {
-            self.current -= 1;
-            println!("wedwefqwefqwefqwefpk3,ewplf,d1p3l,ef[p1l3,ef13[{:?}ple,f", self.current);
             None
         };
 
-        Ok(StmtNode::IfStatement(condition, Box::new(then_branch), else_branch.map(Box::new)))
+        let span = start.to(self.previous_span());
+        let Some(condition) = condition else {
+            return Ok(Stmt::new(StmtNode::Empty, span));
+        };
+        let node = StmtNode::IfStatement(condition, Box::new(then_branch), else_branch.map(Box::new));
+        Ok(Stmt::new(node, span))
     }
 
-    fn parse_declaration_without_semicolon(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let variable_type = self.tokens[self.current].token_type.clone();
-        self.current += 1; // Consume the type identifier
-
-        let variable_token = self.tokens[self.current].clone();
-        if variable_token.token_global != TokenGlobal::Variable {
-            self.errors.push(self.error("Expected a variable", "Error"));
-        }
-        let variable_name = variable_token.lexeme;
-            self.declared_variables.insert(variable_name.clone(), (variable_type.clone(), "none".to_string()));
-            self.declared_variables.get(&variable_name).cloned().map(|(token_type, _)| token_type).ok_or_else(|| self.error(&format!("Undeclared variable '{}'", variable_name), "Error"));
-
-        let assignment = self.parse_assignment_without_semicolon()?;
-
+    fn parse_for_statement(&mut self) -> Result<Stmt, ErrorMessage> {
+        let start = self.expect(TokenType::For, "Expected 'for'")?.span;
+        let header = self.parse_header(Self::parse_for_header);
+        let body = self.parse_loop_body("for")?;
 
-        Ok(assignment)
+        let span = start.to(self.previous_span());
+        let Some((initialization, condition, increment)) = header else {
+            return Ok(Stmt::new(StmtNode::Empty, span));
+        };
+        let node = StmtNode::ForLoop(initialization.map(Box::new), condition, increment, Box::new(body));
+        Ok(Stmt::new(node, span))
     }
 
-    fn parse_assignment_without_semicolon(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let variable_name = self.tokens[self.current].lexeme.clone();
-
-        let variable_type = self.declared_variables.get(&variable_name).cloned().unwrap_or((TokenType::Error, "none".to_string())).0;
+    // The `(init; condition; increment)` of a for-loop, each part optional.
+    fn parse_for_header(&mut self) -> Result<ForHeader, ErrorMessage> {
+        self.expect(TokenType::OpenParen, "Expected '('")?;
 
-        if !self.is_variable_declared(&variable_name) {
-            self.errors.push(self.error(&format!("Use of undeclared variable '{}'", variable_name), "Error"));
-        }
-        self.current += 1; // Consume the variable
-
-        let operator = self.tokens[self.current].token_type.clone();
-        self.current += 1; // Consume the operator
-
-        let right = match operator {
-            TokenType::Plus => {
-                if self.tokens[self.current].token_type == TokenType::Plus {
-                    self.current += 1; // Consume the second '+'
-                    let val = self.get_variable_value(&variable_name);
-                    let int_val = val.and_then(|v| v.parse::<i32>().ok());
-                    ExprNode::IntLiteral(int_val.unwrap() + 1) // Handle i++
-                } else if self.tokens[self.current].token_type == TokenType::Assignment {
-                    self.current += 1; // Consume the '=' i+=1
-                    let increment_value = match self.parse_expression()? {
-                        ExprNode::IntLiteral(value) => value,
-                        _ => return Err(self.error("Expected an integer value for increment", "Error")),
-                    };
-                    let current_value = self.get_variable_value(&variable_name)
-                        .and_then(|v| v.parse::<i32>().ok())
-                        .unwrap_or(0);
-                    let updated_value = current_value + increment_value;
-                    self.update_variable_value(variable_name.clone(), updated_value.to_string())?;
-                    ExprNode::IntLiteral(updated_value) // Handle i += value
-                } else {
-                    return Err(self.error("Expected '+' or '=' after '+'", "Error"));
-                }
-            },
-            TokenType::Minus => {
-                if self.tokens[self.current].token_type == TokenType::Minus {
-                    self.current += 1; // Consume the second '+'
-                    let val = self.get_variable_value(&variable_name);
-                    let int_val = val.and_then(|v| v.parse::<i32>().ok());
-                    ExprNode::IntLiteral(int_val.unwrap() - 1) // Handle i--
-                } else if self.tokens[self.current].token_type == TokenType::Assignment {
-                    self.current += 1; // Consume the '='
-                    self.parse_expression()? // Handle i -= 1
-                } else {
-                    return Err(self.error("Expected '+' or '=' after '+'", "Error"));
-                }
-            },
-            TokenType::Assignment => { // x = x + 1
-                if self.tokens[self.current].token_type == TokenType::Variable
-                    && self.is_variable_declared(&self.tokens[self.current].lexeme) {
-                    let variable_name = self.tokens[self.current].lexeme.clone();
-                    self.current += 1; // Consume the variable
-                    let variable_value = match self.declared_variables.get(&variable_name) {
-                        Some((_, value)) => value.parse::<i32>().unwrap_or(0),
-                        None => return Err(self.error("Variable not found", "Error")),
-                    };
-                    let updated_value = variable_value + 1;
-                    self.update_variable_value(variable_name.clone(), updated_value.to_string())?;
-                    ExprNode::IntLiteral(updated_value) // Handle i = i + 1
-                } else {
-                    self.parse_expression()? // Handle i = expression
-                }
-            },
-            _ => return Err(self.error("Expected an assignment operator", "Error")),
+        let initialization = if self.check(TokenType::Semicolon) {
+            None
+        } else if is_variable_type(&self.peek().token_type) {
+            Some(self.parse_declaration_without_semicolon()?)
+        } else {
+            let expr = self.parse_expression()?;
+            let span = expr.span;
+            Some(Stmt::new(StmtNode::Expression(expr), span))
         };
+        self.expect(TokenType::Semicolon, "Expected ';'")?;
 
+        let condition = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.parse_condition()?)
+        };
+        self.expect(TokenType::Semicolon, "Expected ';'")?;
 
+        let increment = if self.check(TokenType::CloseParen) {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.expect(TokenType::CloseParen, "Expected ')'")?;
+        Ok((initialization, condition, increment))
+    }
 
-        let expr = ExprNode::Binary(Box::new(ExprNode::Variable(variable_name.clone())), operator, Box::new(right.clone()));
+    fn parse_while_loop(&mut self) -> Result<Stmt, ErrorMessage> {
+        let start = self.expect(TokenType::While, "Expected 'while'")?.span;
+        let condition = self.parse_header(Self::parse_parenthesized_condition);
 
-        self.declared_variables.insert(variable_name.clone(), (variable_type.clone(), right.to_string()));
+        let body = self.parse_loop_body("while")?;
 
-        Ok(StmtNode::Assignment(variable_name, expr))
+        let span = start.to(self.previous_span());
+        let Some(condition) = condition else {
+            return Ok(Stmt::new(StmtNode::Empty, span));
+        };
+        Ok(Stmt::new(StmtNode::WhileLoop(condition, Box::new(body)), span))
     }
 
-    fn parse_single_statement(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let stmt = self.parse_statement()?;
-        println!("stmt: {:?}", stmt);
-        self.current -= 1;
+    fn parse_do_while_loop(&mut self) -> Result<Stmt, ErrorMessage> {
+        let start = self.expect(TokenType::Do, "Expected 'do'")?.span;
 
-        // println!("current parse_single_statement: {:?}", self.current);
-        if self.match_token(TokenType::Semicolon).is_none() {
-            println!("Fiiiiirrrrreeeee: {:?}", self.tokens[self.current]);
-            self.errors.push(self.error("Expected a semicolon", "Error"));
-        }
-        Ok(stmt)
-    }
+        let body = self.parse_loop_body("do")?;
 
-    fn parse_for_statement(&mut self) -> Result<StmtNode, ErrorMessage> {
-        let mut initialization: Option<StmtNode> = None;
-        let mut condition: Option<ExprNode> = None;
-        let mut increment: Option<StmtNode> = None;
-        let mut statement = StmtNode::Block(vec![]);
+        self.expect(TokenType::While, "Expected 'while'")?;
+        let condition = self.parse_header(Self::parse_parenthesized_condition);
+        self.expect(TokenType::Semicolon, "Expected ';'")?;
 
-        // Parsing the 'for' keyword
-        if self.match_token(TokenType::For).is_none() {
-            self.errors.push(self.error("Expected 'for'", "Error"));
-        }
+        let span = start.to(self.previous_span());
+        let Some(condition) = condition else {
+            return Ok(Stmt::new(StmtNode::Empty, span));
+        };
+        Ok(Stmt::new(StmtNode::DoWhileLoop(Box::new(body), condition), span))
+    }
 
-        // Parsing the '('
-        if self.match_token(TokenType::OpenParen).is_none() {
-            self.errors.push(self.error("Expected '('", "Error"));
+    fn parse_jump(&mut self) -> Result<Stmt, ErrorMessage> {
+        let keyword = self.advance();
+        self.expect(TokenType::Semicolon, "Expected ';'")?;
+        let span = keyword.span.to(self.previous_span());
+        // `break` leaves the innermost loop or switch, `continue` only ever a loop
+        match keyword.token_type {
+            TokenType::Break if self.loops + self.switches == 0 => {
+                Err(ErrorMessage::new("'break' outside of a loop or switch", "Error", span))
+            },
+            TokenType::Continue if self.loops == 0 => Err(ErrorMessage::new("'continue' outside of a loop", "Error", span)),
+            TokenType::Break => Ok(Stmt::new(StmtNode::Break, span)),
+            _ => Ok(Stmt::new(StmtNode::Continue, span)),
         }
+    }
 
-        // Parsing initialization
-        initialization = if self.tokens[self.current].token_type != TokenType::Semicolon {
-            if self.tokens[self.current].token_global == TokenGlobal::Identifier {
-                Some(self.parse_declaration_without_semicolon()?)
-            } else {
-                Some(self.parse_assignment_without_semicolon()?)
-            }
-        } else {
-            None
+    fn parse_return(&mut self) -> Result<Stmt, ErrorMessage> {
+        let start = self.expect(TokenType::Return, "Expected 'return'")?.span;
+        let Some((return_type, function)) = self.function.clone() else {
+            return Err(ErrorMessage::new("'return' outside of a function", "Error", start));
         };
-
-        // Parsing the first ';'
-        if self.match_token(TokenType::Semicolon).is_none() {
-            self.errors.push(self.error("Expected ';'", "Error"));
+        let value = if self.check(TokenType::Semicolon) { None } else { Some(self.parse_expression()?) };
+        self.expect(TokenType::Semicolon, "Expected ';'")?;
+
+        let span = start.to(self.previous_span());
+        match (&value, return_type == TokenType::Void) {
+            (Some(_), true) => {
+                let message = format!("Void function '{}' should not return a value", function);
+                Err(ErrorMessage::new(&message, "Error", span))
+            },
+            (None, false) => {
+                let message = format!("Non-void function '{}' should return a value", function);
+                Err(ErrorMessage::new(&message, "Error", span))
+            },
+            _ => Ok(Stmt::new(StmtNode::Return(value), span)),
         }
+    }
 
-            let mut last_token_loop = self.current;
-        'parse_for: loop {
-            let before_cond = self.current;
-            let mut ret = false;
-            println!(" NOTOTOTOTOTOTO   {}", self.current);
-            // Parsing the condition
-            condition = if self.tokens[self.current].token_type != TokenType::Semicolon {
-                let condition_expr = self.parse_condition()?;
-                match &condition_expr {
-                    ExprNode::BoolLiteral(value) => {
-                        if *value {
-                            ret = true;
-                        } else {
-                            println!("Value boolean {:?}", *value);
-                        }
-                    },
-                    _ => (),
+    // One `case` or `default` label with the statements under it. A mistake in
+    // the label is reported and the label skipped, so the statements are still
+    // checked inside the switch; the clause is then left out.
+    fn parse_case_clause(&mut self) -> Option<(Option<Expr>, Vec<Stmt>)> {
+        let case_expr = match self.parse_case_label() {
+            Ok(case_expr) => Some(case_expr),
+            Err(e) => {
+                self.report(e);
+                while !self.is_at_end() {
+                    match self.peek().token_type {
+                        TokenType::Colon | TokenType::Semicolon => {
+                            self.current += 1;
+                            break;
+                        },
+                        TokenType::Case | TokenType::Default | TokenType::CloseBrace => break,
+                        _ => self.current += 1,
+                    }
                 }
-                Some(condition_expr)
-            } else {
                 None
-            };
-
-
-
-            // Parsing the second ';'
-            if self.match_token(TokenType::Semicolon).is_none() {
-                self.errors.push(self.error("Expected ';'", "Error"));
-            }
-
-            if ret {
-                let increment = if self.tokens[self.current].token_type != TokenType::CloseParen {
-                    Some(self.parse_assignment_without_semicolon()?)
-                } else {
-                    None
-                };
-
-            } else {
-                self.current += 3;
-            }
-            println!("{}", self.declared_variables.get("i").unwrap().1);
-
-
-            // Parsing the ')'
-            if self.match_token(TokenType::CloseParen).is_none() {
-                self.errors.push(self.error("Expected ')'", "Error"));
-            }
-
-
-            // Parsing the statement block
-
-            if ret {
-                statement = self.parse_block(0)?;
-            }
-            if ret == false {
-                println!("laaaaaaasssstttt {}", last_token_loop);
-
-                self.current = last_token_loop;
+            },
+        };
 
-                break
-            }
-            println!("i value: {:?}", self.declared_variables.get("i").unwrap().1);
-
-            // Check the condition and decide whether to loop again
-            if ret {
-                last_token_loop = self.current - 1;
-                self.current = before_cond;
-                ret = false;
-                continue 'parse_for;
+        // Without a `break` the statements run on into the next case
+        let mut statements = Vec::new();
+        while !self.is_at_end() && !self.check(TokenType::Case) && !self.check(TokenType::Default) && !self.check(TokenType::CloseBrace) {
+            let start = self.begin_statement();
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.report(e);
+                    self.synchronize(start, true);
+                },
             }
-
-            break 'parse_for;
         }
 
-        Ok(StmtNode::ForLoop(
-            initialization.map(Box::new).unwrap_or(Box::new(StmtNode::Block(vec![]))),
-            condition.map(Box::new).unwrap_or(Box::new(ExprNode::BoolLiteral(true))),
-            increment.map(Box::new).unwrap_or(Box::new(StmtNode::Block(vec![]))),
-            Box::new(statement)
-        ))
+        case_expr.map(|case_expr| (case_expr, statements))
     }
 
-    fn parse_while_loop(&mut self) -> Result<StmtNode, ErrorMessage> {
-        println!("ANA GEEEEEEET");
-        if self.match_token(TokenType::While).is_none() {
-            return Err(self.error("Expected 'while'", "Error"));
-        }
-
-        if self.match_token(TokenType::OpenParen).is_none() {
-            return Err(self.error("Expected '('", "Error"));
-        }
-
-        let condition = self.parse_condition()?;
-
-        if self.match_token(TokenType::CloseParen).is_none() {
-            return Err(self.error("Expected ')'", "Error"));
-        }
-
-        let body = self.parse_block(0)?;
-
-        Ok(StmtNode::WhileLoop(Box::new(condition), Box::new(body)))
+    fn parse_case_label(&mut self) -> Result<Option<Expr>, ErrorMessage> {
+        let case_expr = if self.match_token(TokenType::Default).is_some() {
+            None
+        } else {
+            self.expect(TokenType::Case, "Expected 'case'")?;
+            Some(self.parse_expression()?)
+        };
+        self.expect(TokenType::Colon, "Expected ':'")?;
+        Ok(case_expr)
     }
 
-    fn parse_do_while_loop(&mut self) -> Result<StmtNode, ErrorMessage> {
-        if self.match_token(TokenType::Do).is_none() {
-            return Err(self.error("Expected 'do'", "Error"));
+    fn parse_switch_case(&mut self) -> Result<Stmt, ErrorMessage> {
+        let start = self.expect(TokenType::Switch, "Expected 'switch'")?.span;
+        let subject = self.parse_header(|parser| {
+            parser.expect(TokenType::OpenParen, "Expected '('")?;
+            let subject = parser.parse_expression()?;
+            parser.expect(TokenType::CloseParen, "Expected ')'")?;
+            Ok(subject)
+        });
+        self.expect(TokenType::OpenBrace, "Expected '{'")?;
+
+        let mut cases: Vec<(Option<Expr>, Vec<Stmt>)> = Vec::new();
+        self.switches += 1;
+        while self.check(TokenType::Case) || self.check(TokenType::Default) {
+            let label = self.peek().span;
+            match self.parse_case_clause() {
+                Some(case) if case.0.is_none() && cases.iter().any(|(existing, _)| existing.is_none()) => {
+                    self.report(ErrorMessage::new("Multiple default labels in one switch", "Error", label));
+                },
+                Some(case) => cases.push(case),
+                None => (),
+            }
         }
+        self.switches -= 1;
 
-        let body = self.parse_block(1)?;
-        // self.current += 1;
-        println!("current parse_do_while_loop: {:?}", self.current);
+        self.expect(TokenType::CloseBrace, "Expected '}'")?;
 
-        if self.match_token(TokenType::While).is_none() {
-            return Err(self.error("Expected 'while'", "Error"));
-        }
-
-        if self.match_token(TokenType::OpenParen).is_none() {
-            return Err(self.error("Expected '('", "Error"));
-        }
+        let span = start.to(self.previous_span());
+        let Some(subject) = subject else {
+            return Ok(Stmt::new(StmtNode::Empty, span));
+        };
+        Ok(Stmt::new(StmtNode::SwitchCase(subject, cases), span))
+    }
 
+    fn parse_parenthesized_condition(&mut self) -> Result<Expr, ErrorMessage> {
+        self.expect(TokenType::OpenParen, "Expected '('")?;
         let condition = self.parse_condition()?;
+        self.expect(TokenType::CloseParen, "Expected ')'")?;
+        Ok(condition)
+    }
 
-        if self.match_token(TokenType::CloseParen).is_none() {
-            return Err(self.error("Expected ')'", "Error"));
-        }
-
-        if self.match_token(TokenType::Semicolon).is_none() {
-            return Err(self.error("Expected ';'", "Error"));
+    // A condition that is a bare literal is accepted but almost always a mistake.
+    fn parse_condition(&mut self) -> Result<Expr, ErrorMessage> {
+        let condition = self.parse_expression()?;
+        let always = match condition.node {
+            ExprNode::BoolLiteral(value) => Some(value),
+            ExprNode::IntLiteral(value) => Some(value != 0),
+            _ => None,
+        };
+        match always {
+            Some(true) => self.warnings.push(ErrorMessage::new("Warning: This condition is always true", "Warning", condition.span)),
+            Some(false) => self.warnings.push(ErrorMessage::new("Warning: This condition is always false", "Warning", condition.span)),
+            None => (),
         }
-
-        Ok(StmtNode::DoWhileLoop(Box::new(condition), Box::new(body)))
+        Ok(condition)
     }
 
-    fn parse_case_clause(&mut self) -> Result<(ExprNode, StmtNode), ErrorMessage> {
-        if self.match_token(TokenType::Case).is_none() {
-            return Err(self.error("Expected 'case'", "Error"));
-        }
-        let case_expr = self.parse_expression()?;
-        println!("Expr: {:?}", case_expr);
-        if self.match_token(TokenType::Colon).is_none() {
-            return Err(self.error("Expected ':'", "Error"));
-        }
+    // A full expression, commas included. Where a comma separates arguments,
+    // declarators or initializers, parse_precedence stops short of it instead.
+    fn parse_expression(&mut self) -> Result<Expr, ErrorMessage> {
+        self.parse_precedence(COMMA_PRECEDENCE)
+    }
 
-        let case_stmt = if self.tokens[self.current].token_type == TokenType::For ||  self.tokens[self.current].token_type == TokenType::While || self.tokens[self.current].token_type == TokenType::Do || self.tokens[self.current].token_type == TokenType::If {
-            // self.current += 1;
-            println!("IAM GOING TO WHULE");
-            self.parse_statement()?
-        } else if self.tokens[self.current].token_type != TokenType::Break {
-            self.parse_single_statement()?
-        } else {
-            StmtNode::Block(vec![]) // Add an else branch that returns an empty StmtNode
-        };
-        println!("current parse_case_clause: {:?} {:?}", self.current, self.tokens[self.current]);
+    // Precedence climbing: parses an operand, then folds in every binary operator
+    // that binds at least as tightly as `min_precedence`. Left associative operators
+    // parse their right operand one level tighter; assignment and `?:` recurse at
+    // their own level, which makes them right associative. Every operator and
+    // parenthesis takes the parser a level deeper, as deep as the tree it builds.
+    fn parse_precedence(&mut self, min_precedence: u8) -> Result<Expr, ErrorMessage> {
+        self.nested(|parser| parser.parse_operators(min_precedence))
+    }
 
-        // Check for 'break' statement
-        if self.match_token(TokenType::Break).is_none() {
-            return Err(self.error("Expected 'break'", "Error"));
+    fn parse_operators(&mut self, min_precedence: u8) -> Result<Expr, ErrorMessage> {
+        let mut expr = self.parse_unary()?;
+
+        while let Some(precedence) = self.current_precedence().filter(|precedence| *precedence >= min_precedence) {
+            // What was parsed so far becomes the left operand
+            self.descend()?;
+            let operator = self.advance();
+            let start = expr.span;
+            let node = match precedence {
+                ASSIGNMENT_PRECEDENCE => {
+                    if !is_assignable(&expr) {
+                        return Err(ErrorMessage::new("Left side of an assignment must be a variable", "Error", operator.span));
+                    }
+                    let value = self.parse_precedence(ASSIGNMENT_PRECEDENCE)?;
+                    ExprNode::Assign(Box::new(expr), operator.token_type, Box::new(value))
+                },
+                CONDITIONAL_PRECEDENCE => {
+                    let then_expr = self.parse_expression()?;
+                    self.expect(TokenType::Colon, "Expected ':' in conditional expression")?;
+                    let else_expr = self.parse_precedence(CONDITIONAL_PRECEDENCE)?;
+                    ExprNode::Ternary(Box::new(expr), Box::new(then_expr), Box::new(else_expr))
+                },
+                _ => {
+                    let right = self.parse_precedence(precedence + 1)?;
+                    ExprNode::Binary(Box::new(expr), operator.token_type, Box::new(right))
+                },
+            };
+            expr = Expr::new(node, start.to(self.previous_span()));
         }
 
-        // Check for semicolon after 'break'
-        if self.match_token(TokenType::Semicolon).is_none() {
-            return Err(self.error("Expected ';'", "Error"));
-        }
+        Ok(expr)
+    }
 
-        Ok((case_expr, case_stmt))
+    fn current_precedence(&mut self) -> Option<u8> {
+        binary_precedence(&self.peek().token_type)
     }
 
-    fn parse_switch_case(&mut self) -> Result<StmtNode, ErrorMessage> {
-        if self.match_token(TokenType::Switch).is_none() {
-            return Err(self.error("Expected 'switch'", "Error"));
+    fn parse_unary(&mut self) -> Result<Expr, ErrorMessage> {
+        let prefix = [TokenType::Minus, TokenType::LogicalNot, TokenType::BitwiseNot, TokenType::PlusPlus, TokenType::MinusMinus];
+        if prefix.contains(&self.peek().token_type) {
+            let operator = self.advance();
+            let operand = self.nested(Self::parse_unary)?;
+            if matches!(operator.token_type, TokenType::PlusPlus | TokenType::MinusMinus) && !is_assignable(&operand) {
+                let message = format!("Operand of '{}' must be a variable", operator.lexeme);
+                return Err(ErrorMessage::new(&message, "Error", operand.span));
+            }
+            let span = operator.span.to(operand.span);
+            return Ok(Expr::new(ExprNode::Unary(operator.token_type, Box::new(operand)), span));
         }
+        self.parse_postfix()
+    }
 
+    fn parse_postfix(&mut self) -> Result<Expr, ErrorMessage> {
+        let mut expr = self.parse_factor()?;
 
-        if self.match_token(TokenType::OpenParen).is_none() {
-            return Err(self.error("Expected '('", "Error"));
+        loop {
+            if matches!(self.peek().token_type, TokenType::OpenParen | TokenType::OpenBracket | TokenType::PlusPlus | TokenType::MinusMinus) {
+                self.descend()?;
+            }
+            if self.match_token(TokenType::OpenParen).is_some() {
+                let ExprNode::Variable(function) = &expr.node else {
+                    return Err(ErrorMessage::new("Only functions can be called", "Error", expr.span));
+                };
+                let function = function.clone();
+                let mut arguments = Vec::new();
+                while !self.check(TokenType::CloseParen) {
+                    arguments.push(self.parse_precedence(ASSIGNMENT_PRECEDENCE)?);
+                    if self.match_token(TokenType::Comma).is_none() {
+                        break;
+                    }
+                }
+                self.expect(TokenType::CloseParen, "Expected ')'")?;
+                let span = expr.span.to(self.previous_span());
+                expr = Expr::new(ExprNode::Call(function, arguments), span);
+            } else if self.match_token(TokenType::OpenBracket).is_some() {
+                let index = self.parse_expression()?;
+                self.expect(TokenType::CloseBracket, "Expected ']'")?;
+                let span = expr.span.to(self.previous_span());
+                expr = Expr::new(ExprNode::Index(Box::new(expr), Box::new(index)), span);
+            } else if let Some(operator) = self.match_token(TokenType::PlusPlus).or_else(|| self.match_token(TokenType::MinusMinus)) {
+                if !is_assignable(&expr) {
+                    let message = format!("Operand of '{}' must be a variable", operator_symbol(&operator));
+                    return Err(ErrorMessage::new(&message, "Error", expr.span));
+                }
+                let span = expr.span.to(self.previous_span());
+                expr = Expr::new(ExprNode::Postfix(Box::new(expr), operator), span);
+            } else {
+                return Ok(expr);
+            }
         }
+    }
 
-        let condition = match self.tokens[self.current].token_type {
-            TokenType::Variable => self.parse_expression()?,
-            _ => return Err(self.error("Expected a variable", "Error")),
+    fn parse_factor(&mut self) -> Result<Expr, ErrorMessage> {
+        let token = self.peek().clone();
+        let node = match &token.token_type {
+            TokenType::OpenParen => {
+                self.current += 1; // Consume the OpenParen token
+                let expr = self.parse_expression()?;
+                self.expect(TokenType::CloseParen, "Expected a closing parenthesis")?;
+                return Ok(Expr::new(expr.node, token.span.to(self.previous_span())));
+            },
+            TokenType::IntegerLiteral => ExprNode::IntLiteral(self.int_literal_value(&token)?),
+            TokenType::FloatingLiteral => {
+                match &token.literal {
+                    // An 'f' suffix makes the literal a float, otherwise it is a double
+                    Some(LiteralValue::Floating { value, suffix }) if suffix.eq_ignore_ascii_case("f") => {
+                        let value = *value as f32;
+                        if value.is_infinite() {
+                            return Err(self.error(&format!("Floating literal '{}' does not fit in type 'float'", token.lexeme), "Error"));
+                        }
+                        ExprNode::FloatLiteral(value)
+                    },
+                    Some(LiteralValue::Floating { value, .. }) => ExprNode::DoubleLiteral(*value),
+                    _ => return Err(self.error("Expected a valid float", "Error")),
+                }
+            },
+            TokenType::CharacterLiteral => {
+                match token.literal {
+                    Some(LiteralValue::Character(value)) => ExprNode::CharLiteral(value),
+                    _ => return Err(self.error("Expected a valid character", "Error")),
+                }
+            },
+            TokenType::StringLiteral => {
+                match token.literal {
+                    Some(LiteralValue::String(ref value)) => ExprNode::StringLiteral(value.clone()),
+                    _ => return Err(self.error("Expected a valid string", "Error")),
+                }
+            },
+            TokenType::BooleanLiteral => {
+                match token.lexeme.parse::<bool>() {
+                    Ok(value) => ExprNode::BoolLiteral(value),
+                    Err(_) => return Err(self.error("Expected a valid boolean", "Error")),
+                }
+            },
+            TokenType::Variable => ExprNode::Variable(token.lexeme.clone()),
+            _ => return Err(self.error("Expected a number, variable, or expression", "Error")),
         };
+        self.current += 1; // Consume the literal or variable token
 
-        println!("I FOUND SWIITTTTCCCCHHHCHCHCHCHCHHC");
-        if self.match_token(TokenType::CloseParen).is_none() {
-            return Err(self.error("Expected ')'", "Error"));
-        }
+        Ok(Expr::new(node, token.span))
+    }
 
-        if self.match_token(TokenType::OpenBrace).is_none() {
-            return Err(self.error("Expected '{'", "Error"));
-        }
+    fn peek(&mut self) -> &Token {
+        self.peek_nth(0)
+    }
 
-        let mut cases = Vec::new();
-        while self.tokens[self.current].token_type == TokenType::Case {
-            let case_clause = self.parse_case_clause()?;
-            cases.push(case_clause);
+    // The token `offset` places ahead of the current one. Past the last token,
+    // and once parsing has stopped, this is an Eof token placed right after it.
+    fn peek_nth(&mut self, offset: usize) -> &Token {
+        let index = self.current + offset;
+        let found = !self.stopped && self.tokens.peek_nth(index).is_some();
+        // Pulling the token may have set aside the lexical error that reaches the limit
+        self.stop_at_too_many_errors();
+        if !found {
+            self.eof.span = match self.tokens.buffered() {
+                0 => Span { line: 1, column: 1, end_line: 1, end_column: 1, ..Span::default() },
+                buffered => {
+                    let last = self.tokens[buffered - 1].span;
+                    Span { start: last.end, line: last.end_line, column: last.end_column, ..last }
+                },
+            };
+            return &self.eof;
         }
+        &self.tokens[index]
+    }
 
-        println!("cases len: {:?}", cases.len());
-        println!("current parse_switch_case: {:?}", self.current);
-
-        if self.match_token(TokenType::CloseBrace).is_none() {
-            return Err(self.error("Expected '}'", "Error"));
+    // Consumes the current token, staying put at the end of the input.
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.token_type != TokenType::Eof {
+            self.current += 1;
         }
+        token
+    }
 
-        Ok(StmtNode::SwitchCase(Box::new(condition), cases))
+    fn check(&mut self, token_type: TokenType) -> bool {
+        self.peek().token_type == token_type
     }
 
-    fn is_at_end(&self) -> bool {
-        // println!("current is_at_end: {:?}", self.current);
-        // println!("tokens len: {:?}", self.tokens.len());
-        self.current >= self.tokens.len()
+    fn is_at_end(&mut self) -> bool {
+        self.check(TokenType::Eof)
     }
 
     fn match_token(&mut self, token_type: TokenType) -> Option<TokenType> {
-        if self.is_at_end() {
-            None
-        } else if self.tokens[self.current].token_type == token_type {
+        if self.check(token_type.clone()) {
             self.current += 1;
             Some(token_type)
         } else {
@@ -1109,72 +1159,175 @@ impl Parser {
         }
     }
 
-    fn advance(&mut self) -> Result<Token, &'static str> {
-        if self.current < self.tokens.len() {
-            self.current += 1;
-            let token = self.tokens[self.current].clone();
-            Ok(token)
+    fn expect(&mut self, token_type: TokenType, message: &str) -> Result<Token, ErrorMessage> {
+        if self.check(token_type) {
+            Ok(self.advance())
         } else {
-            Err("No more tokens")
+            Err(self.error(message, "Error"))
+        }
+    }
+
+    fn expect_variable(&mut self) -> Result<Token, ErrorMessage> {
+        match self.peek().token_global {
+            TokenGlobal::Variable => Ok(self.advance()),
+            _ => Err(self.error("Expected a variable", "Error")),
         }
     }
 
-    fn is_variable_declared(&self, variable_name: &str) -> bool {
-        self.declared_variables.contains_key(variable_name)
+    // Marks the start of a statement in a sequence of them. Nothing before it
+    // is read again apart from the span of the token just consumed, so the
+    // stream drops the rest.
+    fn begin_statement(&mut self) -> usize {
+        self.tokens.release(self.current.saturating_sub(1));
+        self.current
     }
 
-    fn is_valid_variable_type(&self, token_type: &TokenType) -> bool {
-        match token_type {
-            TokenType::Int | TokenType::Float | TokenType::Bool | TokenType::String | TokenType::Double | TokenType::Char => true,
-            _ => false,
+    fn previous_span(&self) -> Span {
+        match self.current {
+            0 => Span::default(),
+            current => self.tokens[current - 1].span,
         }
     }
 
-    fn get_variable_type(&self, variable_name: &str) -> Result<TokenType, ErrorMessage> {
-        self.declared_variables.get(variable_name).cloned().map(|(token_type, _)| token_type).ok_or_else(|| self.error(&format!("use of undeclared variable '{}'", variable_name), "Error"))
+    // Converts an integer literal to its int value, reporting literals that
+    // int cannot hold along with the type C gives them.
+    fn int_literal_value(&mut self, token: &Token) -> Result<i32, ErrorMessage> {
+        match &token.literal {
+            Some(LiteralValue::Integer { value, radix, suffix }) => {
+                if *value <= i32::MAX as u64 {
+                    return Ok(*value as i32);
+                }
+                let message = match literal_type(*value, *radix, suffix) {
+                    Some(type_name) => format!("Integer literal '{}' has type '{}' and does not fit in type 'int'", token.lexeme, type_name),
+                    None => format!("Integer literal '{}' is too large for any integer type", token.lexeme),
+                };
+                Err(ErrorMessage::new(&message, "Error", token.span))
+            },
+            _ => Err(ErrorMessage::new("Expected a valid integer", "Error", token.span)),
+        }
     }
 
-    pub fn update_variable_value(&mut self, variable_name: String, new_value: String) -> Result<(), ErrorMessage> {
-        if let Some((token_type, _)) = self.declared_variables.get(&variable_name) {
-            self.declared_variables.insert(variable_name, (token_type.clone(), new_value));
-            Ok(())
-        } else {
-            Err(self.error(&format!("Undeclared variable '{}'", variable_name), "Error"))
+    // Records a syntax error and carries on. An error at the same place as the
+    // one before it is a knock-on effect of that one and is left out.
+    fn report(&mut self, error: ErrorMessage) {
+        if self.stopped || self.errors.last().is_some_and(|last| last.span == error.span) {
+            return;
+        }
+        self.errors.push(error);
+        self.stop_at_too_many_errors();
+    }
+
+    // Gives up on the rest of the program once the lexical and syntax errors
+    // together reach MAX_ERRORS.
+    fn stop_at_too_many_errors(&mut self) {
+        let syntax_errors = self.errors.iter().filter(|error| error.message_type == "Error").count();
+        if self.tokens.error_count() + syntax_errors >= MAX_ERRORS {
+            self.stopped = true;
         }
     }
 
-    fn get_expr_type(&self, expr: &ExprNode) -> Result<TokenType, ErrorMessage> {
-        match expr {
-            ExprNode::IntLiteral(_) => Ok(TokenType::Int),
-            ExprNode::FloatLiteral(_) => Ok(TokenType::Float),
-            ExprNode::CharLiteral(_) => Ok(TokenType::Char),
-            ExprNode::StringLiteral(_) => Ok(TokenType::String),
-            ExprNode::Variable(name) => self.get_variable_type(name),
-            _ => Err(self.error("Expression is not a literal or a variable", "Error")),
+    // Panic-mode recovery after the statement starting at token `start` failed:
+    // skips past the ';' that ends it, or up to the '}' closing the enclosing
+    // block or the keyword starting the next statement. A block opened along the
+    // way, as in a stray `else { ... }`, is skipped as a whole and ends the
+    // statement. Outside a block a stray '}' is skipped like any other token.
+    fn synchronize(&mut self, start: usize, in_block: bool) {
+        let mut nesting = 0;
+        while !self.is_at_end() {
+            let token_type = self.peek().token_type.clone();
+            match token_type {
+                TokenType::Semicolon if nesting == 0 => {
+                    self.current += 1;
+                    return;
+                },
+                TokenType::OpenBrace => nesting += 1,
+                TokenType::CloseBrace if nesting == 1 => {
+                    self.current += 1;
+                    return;
+                },
+                TokenType::CloseBrace if nesting > 1 => nesting -= 1,
+                TokenType::CloseBrace if in_block => return,
+                // The statement that failed may itself start with a keyword
+                token_type if nesting == 0 && self.current > start && starts_statement(&token_type) => return,
+                _ => (),
+            }
+            self.current += 1;
         }
     }
 
-    pub fn get_variable_value(&self, variable_name: &str) -> Option<String> {
-        self.declared_variables.get(variable_name).map(|(_, value)| value.clone())
+    // Moves past the braces starting at the current token, stopping early at a
+    // ';' when one of them is never closed.
+    fn skip_braces(&mut self) {
+        let mut nesting = 0;
+        while !self.is_at_end() {
+            let token_type = self.peek().token_type.clone();
+            match token_type {
+                TokenType::OpenBrace => nesting += 1,
+                TokenType::CloseBrace => nesting -= 1,
+                TokenType::Semicolon => return,
+                _ => (),
+            }
+            self.current += 1;
+            if nesting == 0 {
+                return;
+            }
+        }
     }
 
-    pub fn get_declared_variables(&self) -> HashMap<String, (TokenType, String)> {
-        self.declared_variables.clone()
+    // Goes a level deeper into the program, refusing past MAX_NESTING levels.
+    fn descend(&mut self) -> Result<(), ErrorMessage> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error("Nesting is too deep", "Error"));
+        }
+        self.depth += 1;
+        Ok(())
     }
 
-    pub fn get_declared_lists(&self) -> HashMap<String, Vec<i32>> {
-        self.process_list.clone()
+    // Runs `parse` a level deeper, back at the current level afterwards.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ErrorMessage>) -> Result<T, ErrorMessage> {
+        let depth = self.depth;
+        let result = self.descend().and_then(|_| parse(self));
+        self.depth = depth;
+        result
     }
 
-    fn error(&self, message: &str, message_type_: &str) -> ErrorMessage {
-        let token = &self.tokens[if self.current >= self.tokens.len() { self.current - 1 } else { self.current }];
-        let error_message = ErrorMessage {
-            message_type: message_type_.to_string(),
-            message: format!("{}", message),
-            line: token.original_line,
-            column: token.original_column,
+    // Parses the parenthesised part of a header such as `while (...)`. An error in
+    // it is reported and the rest of the header skipped, so the body is still checked.
+    fn parse_header<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ErrorMessage>) -> Option<T> {
+        match parse(self) {
+            Ok(header) => Some(header),
+            Err(e) => {
+                self.report(e);
+                let mut nesting = 0;
+                while !self.is_at_end() {
+                    let token_type = self.peek().token_type.clone();
+                    match token_type {
+                        TokenType::OpenParen => nesting += 1,
+                        TokenType::CloseParen if nesting == 0 => {
+                            self.current += 1;
+                            break;
+                        },
+                        TokenType::CloseParen => nesting -= 1,
+                        TokenType::OpenBrace | TokenType::CloseBrace => break,
+                        _ => (),
+                    }
+                    self.current += 1;
+                }
+                None
+            },
+        }
+    }
 
-        };
-        error_message
+    // Reports at the current token. Running out of input is said so, with what
+    // was expected instead: "Unexpected end of input, expected ';'".
+    fn error(&mut self, message: &str, message_type_: &str) -> ErrorMessage {
+        let token = self.peek();
+        if token.token_type != TokenType::Eof {
+            return ErrorMessage::new(message, message_type_, token.span);
+        }
+        let span = token.span;
+        let mut expected = message.chars();
+        let expected: String = expected.next().map(|first| first.to_lowercase().chain(expected).collect()).unwrap_or_default();
+        ErrorMessage::new(&format!("Unexpected end of input, {}", expected), message_type_, span)
     }
-}
\ No newline at end of file
+}
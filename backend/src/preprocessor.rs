@@ -0,0 +1,575 @@
+use std::collections::HashMap;
+use crate::scanner::{Scanner, ScannerOptions};
+use crate::token::{Span, Token, TokenType, TokenGlobal};
+
+// Standard library headers; `#include <...>` of one that is not in the virtual
+// file set is accepted and contributes nothing.
+const STANDARD_HEADERS: [&str; 12] = [
+    "assert.h", "ctype.h", "float.h", "limits.h", "math.h", "stdbool.h",
+    "stddef.h", "stdint.h", "stdio.h", "stdlib.h", "string.h", "time.h",
+];
+
+#[derive(Debug, Clone)]
+struct Macro {
+    // None for object-like macros
+    params: Option<Vec<String>>,
+    body: String,
+}
+
+// A run of preprocessed output and the byte range of the original file it came
+// from. Copied runs map byte for byte; macro expansions and included text map
+// onto the whole invocation or `#include` line.
+#[derive(Debug, Clone)]
+struct Segment {
+    output_start: usize,
+    original_start: usize,
+    original_end: usize,
+    copied: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    original: String,
+    line_starts: Vec<usize>,
+    segments: Vec<Segment>,
+    output_len: usize,
+}
+
+impl SourceMap {
+    fn new(original: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(original.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self { original: original.to_string(), line_starts, segments: Vec::new(), output_len: 0 }
+    }
+
+    fn push(&mut self, len: usize, (original_start, original_end): (usize, usize), copied: bool) {
+        if len == 0 {
+            return;
+        }
+        let output_start = self.output_len;
+        self.output_len += len;
+        if let Some(last) = self.segments.last_mut() {
+            let contiguous = last.original_end == original_start
+                && last.output_start + (last.original_end - last.original_start) == output_start;
+            if copied && last.copied && contiguous {
+                last.original_end = original_end;
+                return;
+            }
+        }
+        self.segments.push(Segment { output_start, original_start, original_end, copied });
+    }
+
+    fn original_offset(&self, offset: usize, is_end: bool) -> usize {
+        // An exclusive end belongs to the segment holding the byte before it
+        let probe = if is_end { offset.saturating_sub(1) } else { offset };
+        if probe >= self.output_len {
+            return self.original.len();
+        }
+        let segment = &self.segments[self.segments.partition_point(|segment| segment.output_start <= probe) - 1];
+        match (segment.copied, is_end) {
+            (true, _) => segment.original_start + (offset - segment.output_start),
+            (false, false) => segment.original_start,
+            (false, true) => segment.original_end,
+        }
+    }
+
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        (line, self.original[line_start..offset].chars().count() + 1)
+    }
+
+    fn span_of(&self, start: usize, end: usize) -> Span {
+        let (line, column) = self.position(start);
+        let (end_line, end_column) = self.position(end);
+        Span { start, end, line, column, end_line, end_column }
+    }
+
+    // Translates a span in the preprocessed code into the original file.
+    pub fn original_span(&self, span: Span) -> Span {
+        let start = self.original_offset(span.start, false);
+        let end = self.original_offset(span.end, true).max(start);
+        self.span_of(start, end)
+    }
+
+    pub fn remap(&self, mut token: Token) -> Token {
+        token.span = self.original_span(token.span);
+        token
+    }
+}
+
+pub struct Preprocessed {
+    pub code: String,
+    pub source_map: SourceMap,
+    // Error tokens for malformed directives and macro invocations
+    pub errors: Vec<Token>,
+}
+
+impl Preprocessed {
+    // Preprocessing errors first, then the scanned tokens with their spans mapped
+    // back onto the original file.
+    pub fn tokens(self, options: ScannerOptions) -> impl Iterator<Item = Token> {
+        let source_map = self.source_map;
+        self.errors.into_iter()
+            .chain(Scanner::with_options(self.code, options).map(move |token| source_map.remap(token)))
+    }
+}
+
+// One level of `#ifdef`/`#ifndef` nesting.
+struct Condition {
+    parent_active: bool,
+    holds: bool,
+    in_else: bool,
+    directive: &'static str,
+    range: (usize, usize),
+}
+
+impl Condition {
+    fn active(&self) -> bool {
+        self.parent_active && self.holds != self.in_else
+    }
+}
+
+// Runs `#include`, `#define` and conditional compilation over `code`, resolving
+// includes against the virtual file set `files`.
+pub fn preprocess(code: &str, files: &HashMap<String, String>) -> Preprocessed {
+    let mut preprocessor = Preprocessor {
+        files,
+        macros: HashMap::new(),
+        output: String::new(),
+        source_map: SourceMap::new(code),
+        errors: Vec::new(),
+        include_stack: Vec::new(),
+    };
+    preprocessor.process_file(code, None);
+    let errors = preprocessor.errors.iter()
+        .map(|(message, (start, end))| Token {
+            token_global: TokenGlobal::Error,
+            token_type: TokenType::Error,
+            lexeme: code[*start..*end].to_string(),
+            span: preprocessor.source_map.span_of(*start, *end),
+            literal: None,
+            diagnostic: Some(message.clone()),
+        })
+        .collect();
+    Preprocessed { code: preprocessor.output, source_map: preprocessor.source_map, errors }
+}
+
+struct Preprocessor<'a> {
+    files: &'a HashMap<String, String>,
+    macros: HashMap<String, Macro>,
+    output: String,
+    source_map: SourceMap,
+    errors: Vec<(String, (usize, usize))>,
+    include_stack: Vec<String>,
+}
+
+impl Preprocessor<'_> {
+    // `include_site` is the `#include` line in the original file that everything
+    // from an included file is attributed to.
+    fn process_file(&mut self, source: &str, include_site: Option<(usize, usize)>) {
+        let mut conditions: Vec<Condition> = Vec::new();
+        let mut in_comment = false;
+        let mut line_start = 0;
+        let mut lines = source.split_inclusive('\n').peekable();
+        while let Some(mut line) = lines.next() {
+            let mut content = line.strip_suffix('\n').unwrap_or(line);
+            let trimmed = content.trim_start();
+            let active = conditions.iter().all(Condition::active);
+            if !in_comment && trimmed.starts_with('#') {
+                // A backslash ending the line splices the next one onto the directive
+                while content.ends_with('\\') {
+                    let Some(next) = lines.next() else {
+                        break;
+                    };
+                    line = &source[line_start..line_start + line.len() + next.len()];
+                    content = line.strip_suffix('\n').unwrap_or(line);
+                }
+                let trimmed = content.trim_start();
+                let start = line_start + content.len() - trimmed.len();
+                let range = (start, line_start + content.trim_end().len());
+                let text = strip_comments(&trimmed[1..].replace("\\\n", ""), &mut in_comment);
+                self.directive(&text, range, include_site, &mut conditions);
+                // The spliced newlines stay in the output, which keeps the lines after it in place
+                for (offset, _) in content.match_indices('\n') {
+                    self.emit("\n", (line_start + offset, line_start + offset + 1), include_site, true);
+                }
+            } else if active {
+                // A macro's argument list may continue onto the following lines
+                while self.unterminated_call(content, in_comment) {
+                    let Some(next) = lines.next_if(|next| !next.trim_start().starts_with('#')) else {
+                        break;
+                    };
+                    line = &source[line_start..line_start + line.len() + next.len()];
+                    content = line.strip_suffix('\n').unwrap_or(line);
+                }
+                self.expand_line(content, line_start, include_site, &mut in_comment);
+            } else {
+                identifiers(content, &mut in_comment);
+            }
+            let newline_start = line_start + content.len();
+            line_start += line.len();
+            self.emit(&line[content.len()..], (newline_start, line_start), include_site, true);
+        }
+        for condition in conditions {
+            self.error(format!("Unterminated '#{}'", condition.directive), condition.range, include_site);
+        }
+    }
+
+    fn emit(&mut self, text: &str, original: (usize, usize), include_site: Option<(usize, usize)>, copied: bool) {
+        let (original, copied) = match include_site {
+            Some(site) => (site, false),
+            None => (original, copied),
+        };
+        self.source_map.push(text.len(), original, copied);
+        self.output.push_str(text);
+    }
+
+    fn error(&mut self, message: String, range: (usize, usize), include_site: Option<(usize, usize)>) {
+        match (include_site, self.include_stack.last()) {
+            (Some(site), Some(file)) => {
+                let message = format!("In included file '{}': {}", file, message);
+                self.errors.push((message, site));
+            },
+            _ => self.errors.push((message, range)),
+        }
+    }
+
+    fn expand_line(&mut self, content: &str, line_start: usize, include_site: Option<(usize, usize)>, in_comment: &mut bool) {
+        let mut expansions = Vec::new();
+        let mut consumed = 0;
+        for (start, end) in identifiers(content, in_comment) {
+            if start < consumed {
+                continue;
+            }
+            if let Some((end, result)) = self.invoke(content, start, end, &mut Vec::new()) {
+                expansions.push((start, end, result));
+                consumed = end;
+            }
+        }
+        let mut copied = 0;
+        for (start, end, result) in expansions {
+            self.emit(&content[copied..start], (line_start + copied, line_start + start), include_site, true);
+            let range = (line_start + start, line_start + end);
+            match result {
+                Ok(text) => self.emit(&text, range, include_site, false),
+                Err(message) => {
+                    self.error(message, range, include_site);
+                    self.emit(&content[start..end], range, include_site, true);
+                },
+            }
+            copied = end;
+        }
+        self.emit(&content[copied..], (line_start + copied, line_start + content.len()), include_site, true);
+    }
+
+    // Whether `content` ends inside the argument list of a function-like macro.
+    fn unterminated_call(&self, content: &str, mut in_comment: bool) -> bool {
+        let mut consumed = 0;
+        for (start, end) in identifiers(content, &mut in_comment) {
+            if start < consumed || !matches!(self.macros.get(&content[start..end]), Some(Macro { params: Some(_), .. })) {
+                continue;
+            }
+            let open = end + (content[end..].len() - content[end..].trim_start().len());
+            if !content[open..].starts_with('(') {
+                continue;
+            }
+            match macro_arguments(content, open) {
+                Some((_, close)) => consumed = close,
+                None => return true,
+            }
+        }
+        false
+    }
+
+    fn directive(&mut self, text: &str, range: (usize, usize), include_site: Option<(usize, usize)>, conditions: &mut Vec<Condition>) {
+        let text = text.trim();
+        let name_len = text.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(text.len());
+        let (name, argument) = (&text[..name_len], text[name_len..].trim());
+        let active = conditions.iter().all(Condition::active);
+        match name {
+            "ifdef" | "ifndef" => {
+                let directive = if name == "ifdef" { "ifdef" } else { "ifndef" };
+                if active && !Scanner::is_identifier(argument) {
+                    self.error(format!("Expected a macro name after '#{}'", directive), range, include_site);
+                }
+                let defined = self.macros.contains_key(argument);
+                conditions.push(Condition { parent_active: active, holds: defined == (name == "ifdef"), in_else: false, directive, range });
+            },
+            "else" => match conditions.last_mut() {
+                Some(condition) if !condition.in_else => condition.in_else = true,
+                Some(_) => self.error("Duplicate '#else'".to_string(), range, include_site),
+                None => self.error("'#else' without matching '#ifdef'".to_string(), range, include_site),
+            },
+            "endif" => {
+                if conditions.pop().is_none() {
+                    self.error("'#endif' without matching '#ifdef'".to_string(), range, include_site);
+                }
+            },
+            _ if !active => {},
+            "define" => match parse_define(argument) {
+                Ok((name, definition)) => {
+                    self.macros.insert(name, definition);
+                },
+                Err(message) => self.error(message, range, include_site),
+            },
+            "undef" => {
+                self.macros.remove(argument);
+            },
+            "include" => self.include(argument, range, include_site),
+            // A lone `#` is the null directive
+            "" if argument.is_empty() => {},
+            _ => {
+                let directive = text.split_whitespace().next().unwrap_or(text);
+                self.error(format!("Unknown preprocessor directive '#{}'", directive), range, include_site);
+            },
+        }
+    }
+
+    fn include(&mut self, target: &str, range: (usize, usize), include_site: Option<(usize, usize)>) {
+        let (file, angled) = if let Some(file) = target.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+            (file, false)
+        } else if let Some(file) = target.strip_prefix('<').and_then(|rest| rest.strip_suffix('>')) {
+            (file, true)
+        } else {
+            self.error("Expected \"file\" or <file> after '#include'".to_string(), range, include_site);
+            return;
+        };
+        match self.files.get(file) {
+            Some(_) if self.include_stack.iter().any(|open| open == file) => {
+                self.error(format!("Recursive include of '{}'", file), range, include_site);
+            },
+            Some(source) => {
+                self.include_stack.push(file.to_string());
+                self.process_file(source, Some(include_site.unwrap_or(range)));
+                self.include_stack.pop();
+            },
+            None if angled && STANDARD_HEADERS.contains(&file) => {},
+            None => self.error(format!("Cannot find include file '{}'", file), range, include_site),
+        }
+    }
+
+    // Expands the macro named by `text[start..end]` if there is one, returning
+    // where the invocation ends and its replacement text.
+    fn invoke(&self, text: &str, start: usize, end: usize, disabled: &mut Vec<String>) -> Option<(usize, Result<String, String>)> {
+        let name = &text[start..end];
+        if disabled.iter().any(|macro_name| macro_name == name) {
+            return None;
+        }
+        let definition = self.macros.get(name)?;
+        let Some(params) = &definition.params else {
+            return Some((end, self.rescan(name, &definition.body, disabled)));
+        };
+        // A function-like macro name without arguments is left alone
+        let open = end + (text[end..].len() - text[end..].trim_start().len());
+        if !text[open..].starts_with('(') {
+            return None;
+        }
+        let Some((mut arguments, close)) = macro_arguments(text, open) else {
+            return Some((text.len(), Err(format!("Unterminated argument list for macro '{}'", name))));
+        };
+        if params.is_empty() && arguments.len() == 1 && arguments[0].trim().is_empty() {
+            arguments.clear();
+        }
+        if arguments.len() != params.len() {
+            let plural = if params.len() == 1 { "" } else { "s" };
+            let message = format!("Macro '{}' expects {} argument{} but got {}", name, params.len(), plural, arguments.len());
+            return Some((close, Err(message)));
+        }
+        Some((close, self.apply(name, params, &definition.body, &arguments, disabled)))
+    }
+
+    fn apply(&self, name: &str, params: &[String], body: &str, arguments: &[&str], disabled: &mut Vec<String>) -> Result<String, String> {
+        let mut values = HashMap::new();
+        for (param, argument) in params.iter().zip(arguments) {
+            values.insert(param.as_str(), self.expand(argument.trim(), disabled)?);
+        }
+        let mut substituted = String::new();
+        let mut copied = 0;
+        for (start, end) in identifiers(body, &mut false) {
+            if let Some(value) = values.get(&body[start..end]) {
+                substituted.push_str(&body[copied..start]);
+                substituted.push_str(value);
+                copied = end;
+            }
+        }
+        substituted.push_str(&body[copied..]);
+        self.rescan(name, &substituted, disabled)
+    }
+
+    // Expands a macro's replacement text with the macro itself disabled, so a
+    // self-referencing macro stops after one step. The padding keeps the result
+    // from gluing onto neighbouring tokens.
+    fn rescan(&self, name: &str, body: &str, disabled: &mut Vec<String>) -> Result<String, String> {
+        disabled.push(name.to_string());
+        let expanded = self.expand(body, disabled);
+        disabled.pop();
+        Ok(format!(" {} ", expanded?))
+    }
+
+    fn expand(&self, text: &str, disabled: &mut Vec<String>) -> Result<String, String> {
+        let mut expanded = String::new();
+        let mut copied = 0;
+        for (start, end) in identifiers(text, &mut false) {
+            if start < copied {
+                continue;
+            }
+            if let Some((end, result)) = self.invoke(text, start, end, disabled) {
+                expanded.push_str(&text[copied..start]);
+                expanded.push_str(&result?);
+                copied = end;
+            }
+        }
+        expanded.push_str(&text[copied..]);
+        Ok(expanded)
+    }
+}
+
+fn parse_define(text: &str) -> Result<(String, Macro), String> {
+    let name_len = text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(text.len());
+    let name = &text[..name_len];
+    if !Scanner::is_identifier(name) {
+        return Err("Expected a macro name after '#define'".to_string());
+    }
+    // Only a parenthesis directly after the name starts a parameter list
+    let Some(list) = text[name_len..].strip_prefix('(') else {
+        let body = text[name_len..].trim().to_string();
+        return Ok((name.to_string(), Macro { params: None, body }));
+    };
+    let close = list.find(')').ok_or_else(|| format!("Unterminated parameter list in macro '{}'", name))?;
+    let params: Vec<String> = match list[..close].trim() {
+        "" => Vec::new(),
+        params => params.split(',').map(|param| param.trim().to_string()).collect(),
+    };
+    if !params.iter().all(|param| Scanner::is_identifier(param)) {
+        return Err(format!("Invalid parameter list in macro '{}'", name));
+    }
+    let body = list[close + 1..].trim().to_string();
+    Ok((name.to_string(), Macro { params: Some(params), body }))
+}
+
+// Splits the parenthesised argument list opening at `open` on its top-level
+// commas, returning the arguments and the offset just past the closing paren.
+fn macro_arguments(text: &str, open: usize) -> Option<(Vec<&str>, usize)> {
+    let bytes = text.as_bytes();
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut argument_start = open + 1;
+    let mut index = open + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            quote @ (b'"' | b'\'') => index = skip_quoted(bytes, index, quote),
+            b'(' => depth += 1,
+            b')' if depth == 0 => {
+                arguments.push(&text[argument_start..index]);
+                return Some((arguments, index + 1));
+            },
+            b')' => depth -= 1,
+            b',' if depth == 0 => {
+                arguments.push(&text[argument_start..index]);
+                argument_start = index + 1;
+            },
+            _ => {},
+        }
+        index += 1;
+    }
+    None
+}
+
+// Index of the closing quote, or the end of the line for an unterminated literal.
+fn skip_quoted(bytes: &[u8], open: usize, quote: u8) -> usize {
+    let mut index = open + 1;
+    while index < bytes.len() && bytes[index] != quote && bytes[index] != b'\n' {
+        if bytes[index] == b'\\' {
+            index += 1;
+        }
+        index += 1;
+    }
+    index.min(bytes.len())
+}
+
+// Byte ranges of the identifiers in `line` outside comments and literals. `line`
+// may span several lines of a macro invocation; block comment state carries over
+// between calls through `in_comment`.
+fn identifiers(line: &str, in_comment: &mut bool) -> Vec<(usize, usize)> {
+    let bytes = line.as_bytes();
+    let mut found = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        if *in_comment {
+            match line[index..].find("*/") {
+                Some(offset) => {
+                    *in_comment = false;
+                    index += offset + 2;
+                },
+                None => break,
+            }
+            continue;
+        }
+        let start = index;
+        match bytes[index] {
+            b'/' if bytes.get(index + 1) == Some(&b'/') => {
+                index = line[index..].find('\n').map_or(bytes.len(), |offset| index + offset);
+                continue;
+            },
+            b'/' if bytes.get(index + 1) == Some(&b'*') => {
+                *in_comment = true;
+                index += 2;
+                continue;
+            },
+            quote @ (b'"' | b'\'') => index = skip_quoted(bytes, index, quote),
+            byte if byte.is_ascii_alphabetic() || byte == b'_' => {
+                while index < bytes.len() && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_') {
+                    index += 1;
+                }
+                found.push((start, index));
+                continue;
+            },
+            // Numbers such as `10u` or `1e5` must not expand their suffix
+            byte if byte.is_ascii_digit() => {
+                while index < bytes.len() && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_' || bytes[index] == b'.') {
+                    index += 1;
+                }
+                continue;
+            },
+            _ => {},
+        }
+        index += 1;
+    }
+    found
+}
+
+// Directive text with its comments removed. A block comment left open at the end
+// of the line continues onto the following lines.
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let bytes = line.as_bytes();
+    let mut stripped = String::new();
+    let mut copied = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'/' if bytes.get(index + 1) == Some(&b'/') => break,
+            b'/' if bytes.get(index + 1) == Some(&b'*') => {
+                stripped.push_str(&line[copied..index]);
+                stripped.push(' ');
+                match line[index + 2..].find("*/") {
+                    Some(offset) => {
+                        index += offset + 4;
+                        copied = index;
+                    },
+                    None => {
+                        *in_comment = true;
+                        return stripped;
+                    },
+                }
+                continue;
+            },
+            quote @ (b'"' | b'\'') => index = skip_quoted(bytes, index, quote),
+            _ => {},
+        }
+        index += 1;
+    }
+    stripped.push_str(&line[copied..index.min(bytes.len())]);
+    stripped
+}
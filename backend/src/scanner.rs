@@ -1,457 +1,688 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, VecDeque};
+use std::ops::Index;
 use warp::{Rejection, Reply};
 use serde::{Serialize, Deserialize};
-use crate::token::{Token, TokenType, TokenGlobal};
-use crate::parser::Parser;
-use regex::Regex;
+use crate::token::{LiteralValue, Span, Token, TokenType, TokenGlobal};
+use crate::parser::{ErrorMessage, Parser};
+use crate::interpreter::{with_large_stack, Interpreter, ListValue};
+use crate::keywords::LanguageConfig;
+use crate::checker::check;
+use crate::preprocessor::preprocess;
+use crate::symbols::{resolve, Scope};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tokens {
-    tokens: Vec<Token>,
+    pub tokens: Vec<Token>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Code {
     pub code: String,
+    #[serde(flatten)]
+    pub language: LanguageConfig,
+    // Virtual file set that `#include` resolves against, keyed by file name
+    #[serde(default)]
+    pub files: HashMap<String, String>,
 }
 
+// Comments and whitespace are dropped by default; enabling both yields a token
+// stream whose lexemes concatenate back to the original source.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScannerOptions {
+    pub emit_comments: bool,
+    pub emit_whitespace: bool,
+    pub language: LanguageConfig,
+}
+
+// Lexes lazily: each call to `next` scans just far enough to produce one token.
 pub struct Scanner {
     code: String,
-    tokens: Tokens,
+    options: ScannerOptions,
+    keywords: HashMap<&'static str, (TokenType, TokenGlobal)>,
+    pending: VecDeque<Token>,
+    position: usize,
     line: usize,
     column: usize,
-
+    start: usize,
+    start_line: usize,
+    start_column: usize,
 }
 
 impl Scanner {
     pub fn new(code: String) -> Self {
+        Self::with_options(code, ScannerOptions::default())
+    }
+
+    pub fn with_options(code: String, options: ScannerOptions) -> Self {
         Self {
             code,
-            tokens: Tokens {
-                tokens: Vec::new(),
-            },
+            keywords: options.language.keyword_table(),
+            options,
+            pending: VecDeque::new(),
+            position: 0,
             line: 1,
-            column: 0,
-        }
-    }
-    fn split_into_tokens_with_positions(&self) -> Vec<(String, usize)> {
-        let mut tokens = Vec::new();
-        let mut start = 0;
-        let mut in_string_literal = false;
-        let mut in_brackets = false;
-        let mut in_braces = false;
-
-        for (i, c) in self.code.char_indices() {
-            match c {
-                '"' => in_string_literal = !in_string_literal,
-                '[' | ']' => {
-                    if start != i {
-                        let token = &self.code[start..i];
-                        tokens.push((token.to_string(), start));
-                    }
-                    start = i;
-                },
-                '{' => in_braces = true,
-                '}' => in_braces = false,
-                _ => (),
-            }
+            column: 1,
+            start: 0,
+            start_line: 1,
+            start_column: 1,
+        }
+    }
 
-            if !in_string_literal && !in_brackets && !in_braces && c.is_whitespace() {
-                if start != i {
-                    let token = &self.code[start..i];
-                    tokens.push((token.to_string(), start));
-                }
-                start = i + 1;
+    // Starts scanning at `position`, a token boundary whose line and column the
+    // caller already knows, as when re-lexing after an edit.
+    pub(crate) fn resume(code: String, options: ScannerOptions, position: usize, line: usize, column: usize) -> Self {
+        Self {
+            position,
+            line,
+            column,
+            start: position,
+            start_line: line,
+            start_column: column,
+            ..Self::with_options(code, options)
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.code[self.position..].chars().next()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        let mut chars = self.code[self.position..].chars();
+        chars.next();
+        chars.next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn advance_while<F: Fn(char) -> bool>(&mut self, predicate: F) {
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
             }
+            self.advance();
         }
+    }
+
+    fn mark_start(&mut self) {
+        self.start = self.position;
+        self.start_line = self.line;
+        self.start_column = self.column;
+    }
 
-        if start != self.code.len() {
-            let token = &self.code[start..];
-            tokens.push((token.to_string(), start));
+    fn current_span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.position,
+            line: self.start_line,
+            column: self.start_column,
+            end_line: self.line,
+            end_column: self.column,
         }
+    }
+
+    fn current_lexeme(&self) -> &str {
+        &self.code[self.start..self.position]
+    }
 
-        tokens
+    fn push_token(&mut self, token_type: TokenType, token_global: TokenGlobal) {
+        self.pending.push_back(Token {
+            token_type,
+            token_global,
+            lexeme: self.current_lexeme().to_string(),
+            span: self.current_span(),
+            literal: None,
+            diagnostic: None,
+        });
     }
 
-    fn process_comments(&mut self) -> String {
-        let mut cleaned_code = String::new();
-        let lines: Vec<&str> = self.code.split('\n').collect();
-        let mut in_multi_line_comment = false;
-        let mut multi_line_comment = String::new();
+    fn push_literal(&mut self, token_type: TokenType, literal: LiteralValue) {
+        self.push_token(token_type, TokenGlobal::Literal);
+        if let Some(token) = self.pending.back_mut() {
+            token.literal = Some(literal);
+        }
+    }
 
-        for line in lines {
-            if in_multi_line_comment {
-                if let Some(end) = line.find("*/") {
-                    in_multi_line_comment = false;
-                    multi_line_comment.push_str(&line[..end + 2]);
-                    multi_line_comment.clear();
+    fn push_error(&mut self, diagnostic: &str) {
+        self.pending.push_back(Token {
+            token_type: TokenType::Error,
+            token_global: TokenGlobal::Error,
+            lexeme: self.current_lexeme().to_string(),
+            span: self.current_span(),
+            literal: None,
+            diagnostic: Some(diagnostic.to_string()),
+        });
+    }
+
+    fn process_whitespace(&mut self) {
+        self.advance_while(char::is_whitespace);
+        if self.options.emit_whitespace {
+            self.push_token(TokenType::Whitespace, TokenGlobal::Whitespace);
+        }
+    }
+
+    fn process_comments(&mut self) {
+        if self.peek_next() == Some('/') {
+            self.skip_line_comment();
+        } else if !self.skip_block_comment() {
+            self.push_error("Unclosed block comment");
+            return;
+        }
+        if self.options.emit_comments {
+            self.push_token(TokenType::Comment, TokenGlobal::Comment);
+        }
+    }
+
+    fn skip_line_comment(&mut self) {
+        self.advance_while(|c| c != '\n');
+    }
 
-                    cleaned_code.push_str(&" ".repeat(end + 2));
-                    cleaned_code.push_str(&line[end + 2..]);
-                } else {
-                    multi_line_comment.push_str(line);
-                    multi_line_comment.push('\n');
-                    cleaned_code.push_str(&" ".repeat(line.len()));
+    fn skip_block_comment(&mut self) -> bool {
+        // Consume the opening "/*"
+        self.advance();
+        self.advance();
+        while let Some(c) = self.advance() {
+            if c == '*' && self.peek() == Some('/') {
+                self.advance();
+                return true;
+            }
+        }
+        false
+    }
+
+    // Consumes a quoted literal up to the closing quote or the end of the line,
+    // returning whether the closing quote was found.
+    fn lex_quoted(&mut self, quote: char) -> bool {
+        self.advance(); // Consume the opening quote
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.advance();
+            if c == '\\' {
+                if self.peek().is_some_and(|next| next != '\n') {
+                    self.advance();
                 }
+            } else if c == quote {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn process_quoted(&mut self, quote: char) {
+        let terminated = self.lex_quoted(quote);
+        if !terminated {
+            if quote == '"' {
+                self.push_error("Unterminated string literal");
             } else {
-                if let Some(start) = line.find("/*") {
-                    in_multi_line_comment = true;
-                    cleaned_code.push_str(&line[..start]);
-                    cleaned_code.push_str(&" ".repeat(line.len() - start));
-                    multi_line_comment.push_str(&line[start..]);
-                    multi_line_comment.push('\n');
-                } else if let Some(start) = line.find("//") {
-                    cleaned_code.push_str(&line[..start]);
-                    cleaned_code.push_str(&" ".repeat(line.len() - start));
-                } else {
-                    cleaned_code.push_str(line);
-                }
+                self.push_error("Unterminated character literal");
             }
-            cleaned_code.push('\n');
+            return;
         }
 
-        cleaned_code
-    }
+        let lexeme = self.current_lexeme();
+        let value = match Self::decode_escapes(&lexeme[1..lexeme.len() - 1]) {
+            Ok(value) => value,
+            Err(message) => return self.push_error(&message),
+        };
+        if quote == '"' {
+            return self.push_literal(TokenType::StringLiteral, LiteralValue::String(value));
+        }
 
-    fn process_literals(&mut self, potential_token: &str,  original_line: usize, original_column: usize) -> bool {
-        if potential_token.starts_with('"') && potential_token.ends_with('"') {
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::StringLiteral,
-                token_global: TokenGlobal::Literal,
-                lexeme: potential_token.trim_matches('"').to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => self.push_error("Empty character literal"),
+            (Some(c), None) => self.push_literal(TokenType::CharacterLiteral, LiteralValue::Character(c)),
+            (Some(_), Some(_)) => self.push_error("Invalid character literal: expected a single character"),
+        }
+    }
 
-            });
-            self.column += potential_token.len();
-            return true;
-        } else if potential_token.starts_with('\'') && potential_token.ends_with('\'') && potential_token.len() == 3 {
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::CharacterLiteral,
-                token_global: TokenGlobal::Literal,
-                lexeme: potential_token.to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += potential_token.len();
-            return true;
-        } else if potential_token == "true" || potential_token == "false" {
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::BooleanLiteral,
-                token_global: TokenGlobal::Literal,
-                lexeme: potential_token.to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += potential_token.len();
-            return true;
-        } else if potential_token.parse::<i32>().is_ok() {
-            println!("FUCKYEAH");
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::IntegerLiteral,
-                token_global: TokenGlobal::Literal,
-                lexeme: potential_token.to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += potential_token.len();
-            return true;
-        } else if potential_token.parse::<f64>().is_ok() {
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::FloatingLiteral,
-                token_global: TokenGlobal::Literal,
-                lexeme: potential_token.to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += potential_token.len();
-            return true;
+    fn decode_escapes(content: &str) -> Result<String, String> {
+        let mut value = String::new();
+        let mut chars = content.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('\'') => '\'',
+                Some('"') => '"',
+                Some('x') => {
+                    let mut digits = String::new();
+                    while digits.len() < 2 && chars.peek().is_some_and(|d| d.is_ascii_hexdigit()) {
+                        digits.extend(chars.next());
+                    }
+                    match u8::from_str_radix(&digits, 16) {
+                        Ok(byte) => char::from(byte),
+                        Err(_) => return Err("Invalid escape sequence '\\x': expected hexadecimal digits".to_string()),
+                    }
+                },
+                Some('u') => {
+                    let mut digits = String::new();
+                    let mut closed = false;
+                    if chars.next_if_eq(&'{').is_some() {
+                        for d in chars.by_ref() {
+                            if d == '}' {
+                                closed = true;
+                                break;
+                            }
+                            digits.push(d);
+                        }
+                    }
+                    let code_point = u32::from_str_radix(&digits, 16).ok()
+                        .filter(|_| closed && digits.len() <= 6)
+                        .and_then(char::from_u32);
+                    match code_point {
+                        Some(c) => c,
+                        None => return Err(format!("Invalid unicode escape '\\u{{{}}}'", digits)),
+                    }
+                },
+                Some(other) => return Err(format!("Invalid escape sequence '\\{}'", other)),
+                None => return Err("Invalid escape sequence at end of literal".to_string()),
+            };
+            value.push(escaped);
         }
-        return false;
-    }
-
-    fn process_symbols(&mut self, potential_token: &str ,original_line: usize, original_column: usize) -> bool {
-        let symbols: Vec<&str> = ["(", ")", "+", "-", "*", "/", "%", "=", ";", ":", "{", "}", ",", "|", "&", ">", "<", "!", "[", "]"].to_vec();
-        let mut found = false;
-        for symbol in &symbols {
-            if potential_token.contains(symbol) {
-                let token_type = match *symbol {
-                    "(" => TokenType::OpenParen,
-                    ")" => TokenType::CloseParen,
-                    "+" => TokenType::Plus,
-                    "-" => TokenType::Minus,
-                    "*" => TokenType::Multiply,
-                    "/" => TokenType::Divide,
-                    "%" => TokenType::Modulo,
-                    "=" => TokenType::Assignment,
-                    ";" => TokenType::Semicolon,
-                    ":" => TokenType::Colon,
-                    "{" => TokenType::OpenBrace,
-                    "}" => TokenType::CloseBrace,
-                    "," => TokenType::Comma,
-                    "|" => TokenType::BitwiseOr,
-                    "&" => TokenType::BitwiseAnd,
-                    ">" => TokenType::GreaterThan,
-                    "<" => TokenType::LessThan,
-                    "!" => TokenType::Exclamation,
-                    "[" => TokenType::OpenBracket,
-                    "]" => TokenType::CloseBracket,
-
-                    _ => unreachable!(),
-                };
-
-                self.tokens.tokens.push(Token {
-                    token_type,
-                    token_global: TokenGlobal::Symbol,
-                    lexeme: symbol.to_string(),
-                    line: self.line,
-                    column: self.column,
-                    original_line:original_line,
-                    original_column:original_column
-                });
-                self.column += symbol.len();
-                found = true;
+        Ok(value)
+    }
+
+    // Consumes digits (hexadecimal ones when radix is 16), allowing C23 digit
+    // separators such as 1'000'000 between two digits.
+    fn advance_digits(&mut self, radix: u32) {
+        while let Some(c) = self.peek() {
+            let separator = c == '\''
+                && self.code[..self.position].ends_with(|p: char| p.is_digit(radix))
+                && self.peek_next().is_some_and(|n| n.is_digit(radix));
+            if !c.is_digit(radix) && !separator {
+                break;
             }
+            self.advance();
         }
-        found
     }
 
+    fn exponent_follows(&self) -> bool {
+        let rest = &self.code[self.position + 1..];
+        let rest = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+        rest.starts_with(|c: char| c.is_ascii_digit())
+    }
 
-    fn process_identifiers(&mut self, potential_token: &str, original_line: usize, original_column: usize) -> bool {
-        let identifiers: Vec<&str> = ["void", "int", "float", "string", "double", "bool", "char"].to_vec();
+    fn process_number(&mut self) {
+        let mut radix = 10;
+        if self.peek() == Some('0') {
+            match self.peek_next() {
+                Some('x' | 'X') => radix = 16,
+                Some('b' | 'B') => radix = 2,
+                _ => (),
+            }
+        }
+        if radix != 10 {
+            // Consume the "0x" or "0b" prefix
+            self.advance();
+            self.advance();
+        }
 
-        if identifiers.contains(&potential_token) {
-            let token_type = match potential_token {
-                "void" => TokenType::Void,
-                "int" => TokenType::Int,
-                "float" => TokenType::Float,
-                "string" => TokenType::String,
-                "double" => TokenType::Double,
-                "bool" => TokenType::Bool,
-                "char" => TokenType::Char,
-                _ => unreachable!(),
-            };
+        let digits_start = self.position;
+        // Binary literals consume all decimal digits so a stray '2' is reported
+        self.advance_digits(if radix == 16 { 16 } else { 10 });
 
-            self.tokens.tokens.push(Token {
-                token_type,
-                token_global: TokenGlobal::Identifier,
-                lexeme: potential_token.to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += potential_token.len();
-            return true;
+        let mut is_floating = false;
+        if radix == 10 {
+            if self.peek() == Some('.') {
+                self.advance();
+                self.advance_digits(10);
+                is_floating = true;
+            }
+            if matches!(self.peek(), Some('e' | 'E')) && self.exponent_follows() {
+                self.advance();
+                if matches!(self.peek(), Some('+' | '-')) {
+                    self.advance();
+                }
+                self.advance_digits(10);
+                is_floating = true;
+            }
+        }
+        let digits_end = self.position;
+
+        // Anything still attached to the number is taken as its suffix
+        self.advance_while(|c| c.is_alphanumeric() || c == '_' || c == '.');
+
+        let digits: String = self.code[digits_start..digits_end].chars().filter(|c| *c != '\'').collect();
+        if radix == 10 && !is_floating && digits.len() > 1 && digits.starts_with('0') {
+            radix = 8;
+        }
+        let suffix = &self.code[digits_end..self.position];
+        let token_type = if is_floating { TokenType::FloatingLiteral } else { TokenType::IntegerLiteral };
+        match Self::number_value(self.current_lexeme(), &digits, radix, suffix, is_floating) {
+            Ok(literal) => self.push_literal(token_type, literal),
+            Err(message) => self.push_error(&message),
         }
-        false
     }
 
-    fn process_reserved_words(&mut self, potential_token: &str, original_line: usize, original_column: usize) -> bool {
-        let reserved_words: Vec<&str> = ["for", "while", "return", "end", "if", "do", "break", "switch", "case", "continue", "else"].to_vec();
-        let potential_token = potential_token.trim(); // Trim the whitespace
-
-        for reserved_word in &reserved_words {
-            if potential_token.starts_with(reserved_word) {
-                let token_type = match *reserved_word {
-                    "for" => TokenType::For,
-                    "while" => TokenType::While,
-                    "return" => TokenType::Return,
-                    "if" => TokenType::If,
-                    "do" => TokenType::Do,
-                    "break" => TokenType::Break,
-                    "continue" => TokenType::Continue,
-                    "else" => TokenType::Else,
-                    "switch" => TokenType::Switch,
-                    "case" => TokenType::Case,
-
-                    _ => unreachable!(),
-                };
-
-                self.tokens.tokens.push(Token {
-                    token_type,
-                    token_global: TokenGlobal::ReservedWord,
-                    lexeme: reserved_word.to_string(),
-                    line: self.line,
-                    column: self.column,
-                    original_line:original_line,
-                    original_column:original_column
-                });
-                self.column += reserved_word.len();
-                return true;
+    fn number_value(lexeme: &str, digits: &str, radix: u32, suffix: &str, is_floating: bool) -> Result<LiteralValue, String> {
+        if suffix.contains('.') {
+            return Err(format!("Invalid numeric literal '{}'", lexeme));
+        }
+
+        if is_floating {
+            if !matches!(suffix.to_ascii_lowercase().as_str(), "" | "f" | "l") {
+                return Err(format!("Invalid suffix '{}' on floating literal", suffix));
+            }
+            let value = digits.parse::<f64>().map_err(|_| format!("Invalid floating literal '{}'", lexeme))?;
+            if value.is_infinite() {
+                return Err(format!("Floating literal '{}' is out of range", lexeme));
             }
+            return Ok(LiteralValue::Floating { value, suffix: suffix.to_string() });
         }
-        false
+
+        let kind = match radix {
+            16 => "hexadecimal",
+            8 => "octal",
+            2 => "binary",
+            _ => "decimal",
+        };
+        if digits.is_empty() {
+            return Err(format!("Invalid {} literal '{}': expected digits", kind, lexeme));
+        }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Err(format!("Invalid digit '{}' in {} literal", digit, kind));
+        }
+        if !matches!(suffix.to_ascii_lowercase().as_str(), "" | "u" | "l" | "ul" | "lu" | "ll" | "ull" | "llu") {
+            return Err(format!("Invalid suffix '{}' on integer literal", suffix));
+        }
+        let value = u64::from_str_radix(digits, radix).map_err(|_| format!("Integer literal '{}' is too large", lexeme))?;
+        Ok(LiteralValue::Integer { value, radix, suffix: suffix.to_string() })
     }
 
-    fn process_variables(&mut self, potential_token: &str, original_line: usize, original_column: usize) -> bool {
-        // Check if the word is not an identifier or reserved word before classifying it as a variable
-        if !self.tokens.tokens.iter().any(|token| token.lexeme == potential_token && (matches!(token.token_global, TokenGlobal::Identifier) || matches!(token.token_global, TokenGlobal::ReservedWord))) && (Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap().is_match(potential_token) || potential_token.starts_with('_') || Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*\[\d+\]$").unwrap().is_match(potential_token)) {
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::Variable,
-                token_global: TokenGlobal::Variable,
-                lexeme: potential_token.to_string(),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += potential_token.len();
-            return true;
+    fn lex_word(&mut self) -> String {
+        self.advance_while(|c| c.is_alphanumeric() || c == '_');
+        self.current_lexeme().to_string()
+    }
+
+    fn symbol_type(symbol: &str) -> Option<TokenType> {
+        let token_type = match symbol {
+            "<<=" => TokenType::ShiftLeftAssignment,
+            ">>=" => TokenType::ShiftRightAssignment,
+            "==" => TokenType::Equal,
+            "!=" => TokenType::NotEqual,
+            "<=" => TokenType::LessThanOrEqual,
+            ">=" => TokenType::GreaterThanOrEqual,
+            "&&" => TokenType::LogicalAnd,
+            "||" => TokenType::LogicalOr,
+            "++" => TokenType::PlusPlus,
+            "--" => TokenType::MinusMinus,
+            "+=" => TokenType::PlusAssignment,
+            "-=" => TokenType::MinusAssignment,
+            "*=" => TokenType::MultiplyAssignment,
+            "/=" => TokenType::DivideAssignment,
+            "%=" => TokenType::ModuloAssignment,
+            "&=" => TokenType::BitwiseAndAssignment,
+            "|=" => TokenType::BitwiseOrAssignment,
+            "^=" => TokenType::BitwiseXorAssignment,
+            "<<" => TokenType::ShiftLeft,
+            ">>" => TokenType::ShiftRight,
+            "::" => TokenType::DoubleColon,
+            "(" => TokenType::OpenParen,
+            ")" => TokenType::CloseParen,
+            "{" => TokenType::OpenBrace,
+            "}" => TokenType::CloseBrace,
+            "[" => TokenType::OpenBracket,
+            "]" => TokenType::CloseBracket,
+            "+" => TokenType::Plus,
+            "-" => TokenType::Minus,
+            "*" => TokenType::Multiply,
+            "/" => TokenType::Divide,
+            "%" => TokenType::Modulo,
+            "=" => TokenType::Assignment,
+            "<" => TokenType::LessThan,
+            ">" => TokenType::GreaterThan,
+            "!" => TokenType::LogicalNot,
+            "&" => TokenType::BitwiseAnd,
+            "|" => TokenType::BitwiseOr,
+            "^" => TokenType::BitwiseXor,
+            "~" => TokenType::BitwiseNot,
+            "?" => TokenType::QuestionMark,
+            ":" => TokenType::Colon,
+            ";" => TokenType::Semicolon,
+            "," => TokenType::Comma,
+            "." => TokenType::Dot,
+            _ => return None,
+        };
+        Some(token_type)
+    }
+
+    // Maximal munch: try the longest operator first so "<<=" never lexes as "<" "<" "=".
+    fn process_symbols(&mut self) -> bool {
+        let rest = &self.code[self.position..];
+        for length in (1..=3).rev() {
+            let Some(symbol) = rest.get(..length) else {
+                continue;
+            };
+            if let Some(token_type) = Self::symbol_type(symbol) {
+                for _ in 0..length {
+                    self.advance();
+                }
+                self.push_token(token_type, TokenGlobal::Symbol);
+                return true;
+            }
         }
         false
     }
 
+    fn process_keywords(&mut self, potential_token: &str) -> bool {
+        match self.keywords.get(potential_token) {
+            Some((token_type, token_global)) => {
+                self.push_token(token_type.clone(), token_global.clone());
+                true
+            },
+            None => false,
+        }
+    }
 
-    fn process_lists(&mut self, line: &str ,original_line: usize, original_column: usize) -> bool {
-        if line.contains("[") && line.contains("]") && line.contains("{") && line.contains("}") {
-            let parts: Vec<&str> = line.split("{").collect();
+    pub(crate) fn is_identifier(potential_token: &str) -> bool {
+        let mut chars = potential_token.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
 
-            let list_declaration = parts[0].trim_end_matches("=").trim();
-            let list_initialization_part = parts[1].trim_end_matches("}").trim_end_matches(";").trim();
-            let list_length = if list_initialization_part.starts_with("}") {
-                0
-            } else {
-                list_initialization_part.split(',').count()
-            };
-            let list_initialization = format!("{{{} (length: {})", list_initialization_part, list_length);
-            self.tokens.tokens.push(Token {
-                token_type: TokenType::List,
-                token_global: TokenGlobal::List,
-                lexeme: format!("{}  {}", list_declaration, list_initialization),
-                line: self.line,
-                column: self.column,
-                original_line:original_line,
-                original_column:original_column
-            });
-            self.column += line.len();
+    fn process_variables(&mut self, potential_token: &str) -> bool {
+        if Self::is_identifier(potential_token) {
+            self.push_token(TokenType::Variable, TokenGlobal::Variable);
             return true;
         }
         false
     }
 
-    pub fn scan(&mut self) -> Tokens {
-        self.code = self.process_comments();
-        self.line = 1;
-        let lines: Vec<String> = self.code.split('\n').map(|s| s.to_string()).collect();
-        for line in &lines {
-
-            // Add whitespace around equal sign, semicolon, parentheses, and braces
-            let line = line.replace("=", " = ")
-                .replace("+", " + ")
-                .replace("-", " - ")
-                .replace("*", " * ")
-                .replace("/", " / ")
-                .replace("%", " % ")
-                .replace("++", " ++ ")
-                .replace("--", " -- ")
-                .replace("==", " == ")
-                .replace("!=", " != ")
-                .replace("<", " < ")
-                .replace("<=", " <= ")
-                .replace(">", " > ")
-                .replace(">=", " >= ")
-                .replace("&&", " && ")
-                .replace("||", " || ")
-                .replace("!", " ! ")
-                .replace("&", " & ")
-                .replace("|", " | ")
-                .replace("^", " ^ ")
-                .replace("~", " ~ ")
-                .replace("<<", " << ")
-                .replace(">>", " >> ")
-                .replace("?", " ? ")
-                .replace(":", " : ")
-                .replace(";", " ; ")
-                .replace(".", " . ")
-                .replace("::", " :: ")
-                .replace("[", " [ ")
-                .replace("]", " ] ")
-                .replace("(", " ( ")
-                .replace(")", " ) ")
-                .replace("{", " { ")
-                .replace("}", " } ")
-                .replace(",", " , ");
-            self.code = line.clone();
-            let original_lineL = self.line;
-            let original_columnL = self.column;
-            if self.process_lists(&line , original_lineL, original_columnL) {
-                continue;
-            }
-            let potential_tokens: Vec<(String, usize)> = self.split_into_tokens_with_positions();
-            for (potential_token, position) in potential_tokens {
+    fn process_word(&mut self, word: &str) {
+        if self.process_keywords(word) {
+            return;
+        }
+        if !self.process_variables(word) {
+            self.push_error(&format!("Invalid identifier '{}'", word));
+        }
+    }
 
-                let original_token = potential_token.clone();
-                let original_line = self.line;
+    fn lex_token(&mut self) {
+        let Some(c) = self.peek() else {
+            return;
+        };
+        self.mark_start();
+        let next = self.peek_next();
+
+        if c.is_whitespace() {
+            self.process_whitespace();
+        } else if c == '/' && (next == Some('/') || next == Some('*')) {
+            self.process_comments();
+        } else if c == '"' || c == '\'' {
+            self.process_quoted(c);
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            self.process_number();
+        } else if c.is_alphabetic() || c == '_' {
+            let word = self.lex_word();
+            self.process_word(&word);
+        } else if !self.process_symbols() {
+            self.advance();
+            self.push_error(&format!("Unexpected character '{}'", c));
+        }
+    }
 
-                let mut original_column = self.column + position;
-                if original_column > 0 {
-                    original_column -= 1;
-                }
+    // Convenience collector for callers that want the whole token stream at once
+    pub fn scan(self) -> Tokens {
+        Tokens {
+            tokens: self.collect(),
+        }
+    }
+}
 
-                let potential_token = potential_token.replace(" ", "");
+impl Iterator for Scanner {
+    type Item = Token;
 
+    fn next(&mut self) -> Option<Token> {
+        while self.pending.is_empty() && self.position < self.code.len() {
+            self.lex_token();
+        }
+        self.pending.pop_front()
+    }
+}
 
-                if self.process_literals(&potential_token , original_line , original_column) {
-                    continue;
-                }
-                if self.process_symbols(&potential_token ,original_line, original_column) {
-                    continue;
-                }
-                if self.process_identifiers(&potential_token, original_line, original_column) {
-                    continue;
-                }
-                if self.process_reserved_words(&potential_token,original_line, original_column) {
-                    continue;
-                }
-                if self.process_variables(&potential_token,original_line, original_column) {
-                    continue;
-                }
+// Lookahead buffer over a token iterator. Tokens are pulled from the source only
+// when a position past the end of the buffer is requested, and lexical errors are
+// set aside so the consumer only ever sees valid tokens. Positions count from the
+// first token pulled; the consumer releases the ones it will not go back to.
+pub struct TokenStream {
+    source: Box<dyn Iterator<Item = Token>>,
+    buffer: VecDeque<Token>,
+    // Position of the first token in the buffer
+    released: usize,
+    errors: Vec<ErrorMessage>,
+}
 
+impl TokenStream {
+    pub fn new(source: impl Iterator<Item = Token> + 'static) -> Self {
+        Self {
+            source: Box::new(source),
+            buffer: VecDeque::new(),
+            released: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn peek_nth(&mut self, index: usize) -> Option<&Token> {
+        while self.buffered() <= index {
+            let token = self.source.next()?;
+            if token.token_type == TokenType::Error {
+                let message = token.diagnostic.as_deref().unwrap_or("Invalid token");
+                self.errors.push(ErrorMessage::new(message, "Error", token.span));
+            } else {
+                self.buffer.push_back(token);
             }
-            self.line += 1;
-            self.column = 0;
         }
-        self.tokens.clone()
+        self.buffer.get(index - self.released)
+    }
+
+    // Number of tokens pulled from the source so far
+    pub fn buffered(&self) -> usize {
+        self.released + self.buffer.len()
+    }
+
+    // Number of tokens still held, from the first unreleased one on
+    pub fn held(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Drops the tokens before position `before`, which can no longer be looked at
+    pub fn release(&mut self, before: usize) {
+        while self.released < before && self.buffer.pop_front().is_some() {
+            self.released += 1;
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn take_errors(&mut self) -> Vec<ErrorMessage> {
+        std::mem::take(&mut self.errors)
+    }
+}
+
+impl Index<usize> for TokenStream {
+    type Output = Token;
+
+    fn index(&self, index: usize) -> &Token {
+        &self.buffer[index - self.released]
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParserData {
     vars: HashMap<String, (TokenType, String)>,
-    lists: HashMap<String, Vec<i32>>,
+    lists: HashMap<String, Vec<ListValue>>,
+    // Which symbols live in which block of the program
+    scopes: Scope,
+    warnings: Vec<ErrorMessage>,
 }
 
-pub async fn scanning_input_code(code: Code) -> Result<impl Reply, Rejection> {
-    let mut scanner = Scanner::new(code.code);
-    let tokens = scanner.scan();
-
-    for token in &tokens.tokens {
-        println!("{:?}", token);
-    }
-
-    let mut parser = Parser::new(tokens.tokens);
-    match parser.parse_program() {
-        Ok(_) => {
-            let vars = parser.get_declared_variables();
-            let lists = parser.get_declared_lists();
-            println!("Entered Ok match arm");
-            let data = ParserData { vars, lists };
-            Ok(warp::reply::json(&data))
+// Runs a submitted program through every stage, returning what it left
+// behind or everything that stopped it. Warnings come back either way.
+pub fn analyze(code: Code) -> Result<ParserData, Vec<ErrorMessage>> {
+    let options = ScannerOptions { language: code.language, ..ScannerOptions::default() };
+    let mut parser = Parser::new(preprocess(&code.code, &code.files).tokens(options));
+    let mut interpreter = Interpreter::new();
+    let program = parser.parse_program();
+    // Only errors keep the program from running
+    let mut warnings = parser.warnings().to_vec();
+    let result = program.and_then(|mut program| {
+        let (scopes, diagnostics) = resolve(&program);
+        let (resolve_warnings, errors): (Vec<_>, Vec<_>) = diagnostics.into_iter()
+            .partition(|diagnostic| diagnostic.message_type == "Warning");
+        warnings.extend(resolve_warnings);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let errors = check(&mut program);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        interpreter.run(&program).map(|_| scopes)
+    });
+    match result {
+        Ok(scopes) => {
+            let vars = interpreter.get_declared_variables();
+            let lists = interpreter.get_declared_lists();
+            Ok(ParserData { vars, lists, scopes, warnings })
         },
-        Err(errors) => {
-            println!("{:?}", errors);
-            Ok(warp::reply::json(&errors))
+        Err(mut errors) => {
+            errors.extend(warnings);
+            Err(errors)
         },
     }
-}
\ No newline at end of file
+}
+
+pub async fn scanning_input_code(code: Code) -> Result<impl Reply, Rejection> {
+    // Running the program can take a while, so it stays off the async workers.
+    // Parsing and checking recurse as deeply as the program nests, so they get
+    // the interpreter's stack rather than the blocking pool's.
+    let result = tokio::task::spawn_blocking(move || with_large_stack(|| analyze(code))).await.unwrap_or_else(|_| {
+        let span = Span { line: 1, column: 1, end_line: 1, end_column: 1, ..Span::default() };
+        Err(vec![ErrorMessage::new("The program could not be run", "Error", span)])
+    });
+    match result {
+        Ok(data) => Ok(warp::reply::json(&data)),
+        Err(errors) => Ok(warp::reply::json(&errors)),
+    }
+}
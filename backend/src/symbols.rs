@@ -0,0 +1,337 @@
+use crate::parser::{split_index, ErrorMessage, MAX_NESTING, Expr, ExprNode, ProgramNode, Stmt, StmtNode};
+use crate::token::{Span, TokenType};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolKind {
+    Variable,
+    List,
+    Parameter,
+    Function,
+}
+
+// A declared name. For functions the type is the return type, for lists the
+// element type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(rename = "type")]
+    pub symbol_type: TokenType,
+    #[serde(flatten)]
+    pub span: Span,
+    // Whether a value has been assigned yet; only local variables start without one
+    pub initialized: bool,
+    // The length of each dimension of a list
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dimensions: Vec<usize>,
+}
+
+impl Symbol {
+    pub fn new(name: &str, kind: SymbolKind, symbol_type: &TokenType, span: Span) -> Self {
+        Self { name: name.to_string(), kind, symbol_type: symbol_type.clone(), span, initialized: true, dimensions: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScopeKind {
+    Global,
+    // The parameters and outermost block of a function
+    Function,
+    Block,
+    // The declaration in a for-loop header
+    For,
+    Switch,
+}
+
+// One block of the program with the symbols declared directly in it, in
+// declaration order. A symbol lives from its declaration to the end of the span.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    #[serde(flatten)]
+    pub span: Span,
+    pub symbols: Vec<Symbol>,
+    pub children: Vec<Scope>,
+}
+
+impl Scope {
+    fn new(kind: ScopeKind, span: Span) -> Self {
+        Self { kind, span, symbols: Vec::new(), children: Vec::new() }
+    }
+}
+
+// The scopes that are open at one point of the program, innermost last. A
+// closed scope is kept as a child of the one around it, so the whole tree is
+// left over at the end.
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+}
+
+impl SymbolTable {
+    pub fn new(span: Span) -> Self {
+        Self { scopes: vec![Scope::new(ScopeKind::Global, span)] }
+    }
+
+    pub fn enter(&mut self, kind: ScopeKind, span: Span) {
+        self.scopes.push(Scope::new(kind, span));
+    }
+
+    pub fn exit(&mut self) {
+        if self.scopes.len() > 1 {
+            let scope = self.scopes.pop().unwrap();
+            self.scopes.last_mut().unwrap().children.push(scope);
+        }
+    }
+
+    // Adds a symbol to the innermost scope. It may hide a symbol of an enclosing
+    // scope, but not one declared in the same scope.
+    pub fn declare(&mut self, symbol: Symbol) -> Result<(), ErrorMessage> {
+        let scope = self.scopes.last_mut().unwrap();
+        if let Some(existing) = scope.symbols.iter().find(|existing| existing.name == symbol.name) {
+            let message = match existing.kind {
+                SymbolKind::Function => format!("Function '{}' already defined", symbol.name),
+                _ => format!("Variable '{}' already declared", symbol.name),
+            };
+            return Err(ErrorMessage::new(&message, "Error", symbol.span));
+        }
+        scope.symbols.push(symbol);
+        Ok(())
+    }
+
+    // The innermost visible symbol called `name`.
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.symbols.iter().rev().find(|symbol| symbol.name == name))
+    }
+
+    pub fn lookup_mut(&mut self, name: &str) -> Option<&mut Symbol> {
+        self.scopes.iter_mut().rev()
+            .find_map(|scope| scope.symbols.iter_mut().rev().find(|symbol| symbol.name == name))
+    }
+
+    // Whether declarations are currently going into the global scope.
+    pub fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    // Closes every open scope and returns the global one.
+    pub fn finish(mut self) -> Scope {
+        while self.scopes.len() > 1 {
+            self.exit();
+        }
+        self.scopes.pop().unwrap()
+    }
+}
+
+// Resolves every name in the program against the scopes it is used in,
+// returning the scope tree and the names that could not be resolved. Reading a
+// local variable that nothing has been assigned to yet is warned about once;
+// an assignment anywhere earlier in the source counts, whichever branch it is in.
+pub fn resolve(program: &ProgramNode) -> (Scope, Vec<ErrorMessage>) {
+    let span = match (program.statements.first(), program.statements.last()) {
+        (Some(first), Some(last)) => first.span.to(last.span),
+        _ => Span::default(),
+    };
+    let mut resolver = Resolver { table: SymbolTable::new(span), errors: Vec::new(), warned: Vec::new(), depth: 0, undefined: Vec::new() };
+
+    // Functions can be called from anywhere, including before their definition.
+    // A prototype declares the function once more, which is no redefinition.
+    for statement in &program.statements {
+        if let StmtNode::Function(return_type, name, _, _) = &statement.node {
+            resolver.declare(name, SymbolKind::Function, return_type, statement.span);
+        }
+    }
+    for statement in &program.statements {
+        if let StmtNode::Prototype(return_type, name, _) = &statement.node {
+            if !resolver.table.lookup(name).is_some_and(|symbol| symbol.kind == SymbolKind::Function) {
+                resolver.declare(name, SymbolKind::Function, return_type, statement.span);
+                resolver.undefined.push(name.clone());
+            }
+        }
+    }
+    for statement in &program.statements {
+        resolver.statement(statement);
+    }
+    (resolver.table.finish(), resolver.errors)
+}
+
+struct Resolver {
+    table: SymbolTable,
+    errors: Vec<ErrorMessage>,
+    // Declarations already warned about, by where they start
+    warned: Vec<usize>,
+    // How many expressions enclose the one being resolved
+    depth: usize,
+    // Functions that have a prototype but no definition
+    undefined: Vec<String>,
+}
+
+impl Resolver {
+    fn declare(&mut self, name: &str, kind: SymbolKind, symbol_type: &TokenType, span: Span) {
+        self.declare_symbol(Symbol::new(name, kind, symbol_type, span));
+    }
+
+    fn declare_symbol(&mut self, symbol: Symbol) {
+        if let Err(error) = self.table.declare(symbol) {
+            self.errors.push(error);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match &statement.node {
+            StmtNode::Declaration(variable_type, name, value) => {
+                // The initializer is resolved first, so it still sees an outer `name`
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                // Globals without an initializer start out as zero
+                let initialized = value.is_some() || self.table.is_global();
+                self.declare_symbol(Symbol { initialized, ..Symbol::new(name, SymbolKind::Variable, variable_type, statement.span) });
+            },
+            StmtNode::ListDeclaration(element_type, name, dimensions, values) => {
+                values.iter().for_each(|(_, value)| self.expression(value));
+                let dimensions = dimensions.clone();
+                self.declare_symbol(Symbol { dimensions, ..Symbol::new(name, SymbolKind::List, element_type, statement.span) });
+            },
+            StmtNode::Expression(expr) => self.expression(expr),
+            StmtNode::Declarations(declarations) => declarations.iter().for_each(|declaration| self.statement(declaration)),
+            StmtNode::ForLoop(initialization, condition, increment, body) => {
+                self.table.enter(ScopeKind::For, statement.span);
+                if let Some(initialization) = initialization {
+                    self.statement(initialization);
+                }
+                condition.iter().chain(increment).for_each(|expr| self.expression(expr));
+                self.statement(body);
+                self.table.exit();
+            },
+            StmtNode::IfStatement(condition, then_branch, else_branch) => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            },
+            StmtNode::WhileLoop(condition, body) => {
+                self.expression(condition);
+                self.statement(body);
+            },
+            StmtNode::DoWhileLoop(body, condition) => {
+                self.statement(body);
+                self.expression(condition);
+            },
+            StmtNode::SwitchCase(subject, cases) => {
+                self.expression(subject);
+                // All cases share one scope, as they are one block in C
+                self.table.enter(ScopeKind::Switch, statement.span);
+                for (label, statements) in cases {
+                    label.iter().for_each(|label| self.expression(label));
+                    statements.iter().for_each(|statement| self.statement(statement));
+                }
+                self.table.exit();
+            },
+            StmtNode::Block(statements) => {
+                self.table.enter(ScopeKind::Block, statement.span);
+                statements.iter().for_each(|statement| self.statement(statement));
+                self.table.exit();
+            },
+            StmtNode::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            },
+            StmtNode::Function(_, _, parameters, body) => {
+                // Parameters and the outermost block share a scope, so a local
+                // cannot redeclare a parameter
+                self.table.enter(ScopeKind::Function, statement.span);
+                for (parameter_type, parameter) in parameters {
+                    self.declare(parameter, SymbolKind::Parameter, parameter_type, statement.span);
+                }
+                match &body.node {
+                    StmtNode::Block(statements) => statements.iter().for_each(|statement| self.statement(statement)),
+                    _ => self.statement(body),
+                }
+                self.table.exit();
+            },
+            StmtNode::Prototype(..) | StmtNode::Break | StmtNode::Continue | StmtNode::Empty => (),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        if self.depth >= MAX_NESTING {
+            self.errors.push(ErrorMessage::new("Nesting is too deep", "Error", expr.span));
+            return;
+        }
+        self.depth += 1;
+        match &expr.node {
+            ExprNode::IntLiteral(_) | ExprNode::FloatLiteral(_) | ExprNode::DoubleLiteral(_)
+            | ExprNode::CharLiteral(_) | ExprNode::StringLiteral(_) | ExprNode::BoolLiteral(_) => (),
+            ExprNode::Variable(name) => self.variable(name, expr.span, true),
+            ExprNode::Index(..) => {
+                let (list, indexes) = split_index(expr);
+                match &list.node {
+                    ExprNode::Variable(name) if self.table.lookup(name).is_some_and(|symbol| symbol.kind == SymbolKind::List) => (),
+                    ExprNode::Variable(name) => {
+                        self.errors.push(ErrorMessage::new(&format!("List '{}' not found", name), "Error", list.span));
+                    },
+                    _ => self.errors.push(ErrorMessage::new("Only lists can be indexed", "Error", list.span)),
+                }
+                indexes.into_iter().for_each(|index| self.expression(index));
+            },
+            ExprNode::Unary(_, operand) | ExprNode::Postfix(operand, _) => self.expression(operand),
+            // A plain assignment gives the variable a value without reading it
+            ExprNode::Assign(target, TokenType::Assignment, value) if matches!(target.node, ExprNode::Variable(_)) => {
+                self.expression(value);
+                if let ExprNode::Variable(name) = &target.node {
+                    self.variable(name, target.span, false);
+                }
+            },
+            ExprNode::Binary(left, _, right) | ExprNode::Assign(left, _, right) => {
+                self.expression(left);
+                self.expression(right);
+            },
+            ExprNode::Ternary(condition, then_expr, else_expr) => {
+                self.expression(condition);
+                self.expression(then_expr);
+                self.expression(else_expr);
+            },
+            ExprNode::Call(function, arguments) => {
+                let message = match self.table.lookup(function).map(|symbol| symbol.kind) {
+                    Some(SymbolKind::Function) if self.undefined.contains(function) => {
+                        Some(format!("Function '{}' is declared but never defined", function))
+                    },
+                    Some(SymbolKind::Function) => None,
+                    Some(_) => Some(format!("'{}' is not a function", function)),
+                    None => Some(format!("Call to undeclared function '{}'", function)),
+                };
+                if let Some(message) = message {
+                    self.errors.push(ErrorMessage::new(&message, "Error", expr.span));
+                }
+                arguments.iter().for_each(|argument| self.expression(argument));
+            },
+        }
+        self.depth -= 1;
+    }
+
+    // Resolves a use of a plain variable, which either reads it or assigns to it.
+    fn variable(&mut self, name: &str, span: Span, reading: bool) {
+        let message = match self.table.lookup_mut(name) {
+            Some(symbol) if matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Parameter) => {
+                if !reading {
+                    symbol.initialized = true;
+                } else if !symbol.initialized && !self.warned.contains(&symbol.span.start) {
+                    self.warned.push(symbol.span.start);
+                    let message = format!("Warning: Variable '{}' is used before being assigned", name);
+                    self.errors.push(ErrorMessage::new(&message, "Warning", span));
+                }
+                return;
+            },
+            Some(symbol) if symbol.kind == SymbolKind::List => format!("List '{}' must be indexed", name),
+            Some(_) => format!("Function '{}' must be called", name),
+            None => format!("Use of undeclared variable '{}'", name),
+        };
+        self.errors.push(ErrorMessage::new(&message, "Error", span));
+    }
+}
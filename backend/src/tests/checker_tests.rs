@@ -0,0 +1,133 @@
+use crate::checker::check;
+use crate::parser::{ErrorMessage, Expr, ExprNode, Parser, ProgramNode, Stmt, StmtNode};
+use crate::scanner::Scanner;
+use crate::token::{Span, TokenType};
+
+fn check_code(code: &str) -> (ProgramNode, Vec<ErrorMessage>) {
+    let mut program = Parser::new(Scanner::new(code.to_string())).parse_program().unwrap();
+    let errors = check(&mut program);
+    (program, errors)
+}
+
+fn messages(errors: &[ErrorMessage]) -> Vec<&str> {
+    errors.iter().map(|error| error.message.as_str()).collect()
+}
+
+fn initializer(program: &ProgramNode, index: usize) -> &Expr {
+    match &program.statements[index].node {
+        StmtNode::Declaration(_, _, Some(value)) => value,
+        node => panic!("expected an initialized declaration, got {:?}", node),
+    }
+}
+
+#[test]
+fn checker_types_every_expression() {
+    let (program, errors) = check_code("int a = 2;\nint x = a + 1;\ndouble d = 'c' * 2.5 + a;\nfloat f = 1.5f * true;\nbool b = x > 1 && d != 0;\nint c = 'a' + 'b';");
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(initializer(&program, 1).expr_type, Some(TokenType::Int));
+    assert_eq!(initializer(&program, 3).expr_type, Some(TokenType::Float));
+    assert_eq!(initializer(&program, 4).expr_type, Some(TokenType::Bool));
+    // Two chars still add as ints
+    assert_eq!(initializer(&program, 5).expr_type, Some(TokenType::Int));
+
+    // The operands are typed too: char * double is double, and so is the sum
+    let sum = initializer(&program, 2);
+    assert_eq!(sum.expr_type, Some(TokenType::Double));
+    let ExprNode::Binary(product, _, variable) = &sum.node else { panic!() };
+    assert_eq!(product.expr_type, Some(TokenType::Double));
+    assert_eq!(variable.expr_type, Some(TokenType::Int));
+    let ExprNode::Binary(character, _, _) = &product.node else { panic!() };
+    assert_eq!(character.expr_type, Some(TokenType::Char));
+}
+
+#[test]
+fn checker_reports_mismatches_at_the_expression() {
+    let code = "string s = \"a\";\nint x = s * 2;\nfloat f = 2.5 % 2;\nint y = s;\nbool b = !s;\nif (s) {\n}\nint z = true ? s : 1;\nx += s;\nint a[2] = {1, 2};\nint e = a[1.5];";
+    let (_, errors) = check_code(code);
+    assert_eq!(messages(&errors), [
+        "Operator '*' cannot be applied to values of type 'String' and 'Int'",
+        "Operator '%' cannot be applied to values of type 'Double' and 'Int'",
+        "Cannot assign a String to a variable of type 'Int'",
+        "Operator '!' cannot be applied to a value of type 'String'",
+        "Condition of type 'String' is neither a number nor a boolean",
+        "Operands of '?:' have incompatible types 'String' and 'Int'",
+        "Operator '+' cannot be applied to values of type 'Int' and 'String'",
+        "Expected an integer index",
+    ]);
+    // `s * 2` on line 2
+    assert_eq!((errors[0].span.line, errors[0].span.column, errors[0].span.end_column), (2, 9, 14));
+}
+
+#[test]
+fn checker_checks_calls_returns_and_switches() {
+    let code = "int f(int a, string b) {\n return b;\n}\nvoid g() {\n}\n\
+        int x = f(1);\nint y = f(\"one\", \"two\");\nint z = g();\ng();\n\
+        switch (2.5) {\n case \"a\": x = 1;\n}";
+    let (_, errors) = check_code(code);
+    assert_eq!(messages(&errors), [
+        "Cannot return a String from function 'f' of type 'Int'",
+        "Function 'f' expects 2 argument(s) but got 1",
+        "Argument 1 of 'f' expects type 'Int' but got a String",
+        "Function 'g' does not return a value",
+        "Switch value must be an integer, not 'Double'",
+        "Case label must be an integer, not 'String'",
+    ]);
+}
+
+#[test]
+fn checker_holds_prototypes_to_the_definition() {
+    let (_, errors) = check_code("double f(int);\nint f(int x) {\n return x;\n}\nvoid g(int, char);\nvoid g(int);\nint r = f(1.5, 2);");
+    assert_eq!(messages(&errors), [
+        "Conflicting declarations of function 'f'",
+        "Conflicting declarations of function 'g'",
+        "Function 'f' expects 1 argument(s) but got 2",
+    ]);
+}
+
+#[test]
+fn checker_types_array_elements_and_checks_constant_indexes() {
+    let code = "float a[3] = {1.0, 2.5, 3};\nchar s[] = \"hi\";\nfloat x = a[1] + 1;\nint n = s[3];\nint m = a[-1];\na[3] = 2;\nstring w[2] = {\"a\", 1};";
+    let (program, errors) = check_code(code);
+    assert_eq!(initializer(&program, 2).expr_type, Some(TokenType::Float));
+    assert_eq!(messages(&errors), [
+        "Index 3 is out of bounds for 's' of size 3",
+        "Index -1 is out of bounds for 'a' of size 3",
+        "Index 3 is out of bounds for 'a' of size 3",
+        "Cannot assign an Integer to a variable of type 'String'",
+    ]);
+    assert_eq!((errors[0].span.line, errors[0].span.column), (4, 11));
+}
+
+#[test]
+fn checker_checks_every_index_of_a_multi_dimensional_array() {
+    let code = "int g[2][3];\nint a = g[1][2];\nint b = g[1];\nint c = g[0][3];\nint d = g[2.5][0];";
+    let (program, errors) = check_code(code);
+    assert_eq!(initializer(&program, 1).expr_type, Some(TokenType::Int));
+    assert_eq!(messages(&errors), [
+        "List 'g' expects 2 index(es) but got 1",
+        "Index 3 is out of bounds for 'g' of size 3",
+        "Expected an integer index",
+    ]);
+}
+
+#[test]
+fn checker_types_comma_expressions_by_their_last_operand() {
+    let code = "void f() {\n}\nint main() {\n    int i = 0;\n    double d = (i++, 2.5);\n    for (i = 0; i < 3; i++, f()) {\n    }\n    int n = (i, f());\n    return 0;\n}";
+    let (program, errors) = check_code(code);
+    let StmtNode::Function(.., body) = &program.statements[1].node else { panic!() };
+    let StmtNode::Block(statements) = &body.node else { panic!() };
+    let StmtNode::Declaration(_, _, Some(value)) = &statements[1].node else { panic!() };
+    assert_eq!(value.expr_type, Some(TokenType::Double));
+    assert_eq!(messages(&errors), ["Function 'f' does not return a value"]);
+    assert_eq!((errors[0].span.line, errors[0].span.column), (8, 17));
+}
+
+#[test]
+fn checker_stops_at_deeply_nested_expressions() {
+    // Deeper than the parser would ever build
+    let expr = (0..1000).fold(Expr::new(ExprNode::IntLiteral(1), Span::default()), |operand, _| {
+        Expr::new(ExprNode::Unary(TokenType::Minus, Box::new(operand)), Span::default())
+    });
+    let mut program = ProgramNode { statements: vec![Stmt::new(StmtNode::Expression(expr), Span::default())] };
+    assert_eq!(messages(&check(&mut program)), ["Nesting is too deep"]);
+}
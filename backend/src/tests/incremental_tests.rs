@@ -0,0 +1,82 @@
+use crate::incremental::{relex, TextEdit};
+use crate::scanner::{Scanner, ScannerOptions};
+use crate::token::Token;
+
+fn scan(code: &str, options: &ScannerOptions) -> Vec<Token> {
+    Scanner::with_options(code.to_string(), options.clone()).scan().tokens
+}
+
+fn edit(start: usize, end: usize, replacement: &str) -> TextEdit {
+    TextEdit { start, end, replacement: replacement.to_string() }
+}
+
+// Re-lexing after the edit must agree with scanning the edited source from scratch
+fn assert_relex_matches_full_scan(code: &str, edit: TextEdit, options: &ScannerOptions) -> usize {
+    let previous = scan(code, options);
+    let relexed = relex(code, &previous, &edit, options).unwrap();
+    assert_eq!(relexed.tokens, scan(&relexed.source, options), "after editing {:?} with {:?}", code, edit);
+    relexed.relexed
+}
+
+#[test]
+fn relex_agrees_with_a_full_scan() {
+    let code = "int x = 1;\nfloat y = 2.5; // note\nstring s = \"hi\";\nx += 1e+;\n";
+    let plain = ScannerOptions::default();
+    let trivia = ScannerOptions { emit_comments: true, emit_whitespace: true, ..ScannerOptions::default() };
+    let edits = [
+        edit(4, 5, "count"),            // rename a variable
+        edit(5, 5, "yz"),               // extend an identifier
+        edit(8, 9, "0x1F"),             // change a literal
+        edit(11, 11, "/* "),            // open a comment that swallows the rest
+        edit(48, 49, ""),               // drop a closing quote
+        edit(59, 59, "5"),              // complete an exponent
+        edit(10, 11, "\n\n\t"),         // add lines
+        edit(0, 11, ""),                // delete the first line
+        edit(0, 0, "@"),                // error at the very start
+        edit(code.len(), code.len(), "y"),
+    ];
+    for edit in edits {
+        assert_relex_matches_full_scan(code, edit.clone(), &plain);
+        assert_relex_matches_full_scan(code, edit, &trivia);
+    }
+    assert_relex_matches_full_scan("", edit(0, 0, "int x;"), &plain);
+}
+
+#[test]
+fn relex_rescans_block_comments_that_span_the_edit() {
+    let code = "int x = 1; /* first\nsecond\nthird */ int y = 2;\n/* open\nint z;";
+    let plain = ScannerOptions::default();
+    let trivia = ScannerOptions { emit_comments: true, emit_whitespace: true, ..ScannerOptions::default() };
+    let second = code.find("second").unwrap();
+    let third = code.find("third").unwrap();
+    let open = code.find("open").unwrap();
+    let edits = [
+        edit(second, second + 6, "changed"),   // inside the comment, away from both ends
+        edit(second, second, "*/ int w; /*"),  // close and reopen it in the middle
+        edit(second, second, "*/ "),           // close it early
+        edit(third + 6, third + 8, ""),        // remove the closing "*/"
+        edit(third, third, "\n\n"),            // add lines inside it
+        edit(open, open + 4, "done */"),       // close the unterminated comment
+        edit(second - 1, second, ""),          // join its lines
+    ];
+    for edit in edits {
+        assert_relex_matches_full_scan(code, edit.clone(), &plain);
+        assert_relex_matches_full_scan(code, edit, &trivia);
+    }
+}
+
+#[test]
+fn relex_only_rescans_the_edited_region() {
+    let code: String = (0..1000).map(|i| format!("int v{} = {} + v{};\n", i, i, i)).collect();
+    let line_500 = code.match_indices('\n').nth(499).unwrap().0 + 1;
+    let relexed = assert_relex_matches_full_scan(&code, edit(line_500 + 4, line_500 + 8, "renamed"), &ScannerOptions::default());
+    assert!(relexed <= 8, "re-lexed {} tokens", relexed);
+}
+
+#[test]
+fn relex_rejects_invalid_edit_ranges() {
+    let previous = scan("héllo", &ScannerOptions::default());
+    assert!(relex("héllo", &previous, &edit(3, 1, ""), &ScannerOptions::default()).is_err());
+    assert!(relex("héllo", &previous, &edit(2, 3, ""), &ScannerOptions::default()).is_err());
+    assert!(relex("héllo", &previous, &edit(0, 9, ""), &ScannerOptions::default()).is_err());
+}
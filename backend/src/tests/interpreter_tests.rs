@@ -0,0 +1,328 @@
+use crate::checker::check;
+use crate::interpreter::Interpreter;
+use crate::parser::{ErrorMessage, Parser};
+use crate::scanner::Scanner;
+use crate::symbols::resolve;
+use crate::token::TokenType;
+use serde_json::{json, Value};
+
+fn run(code: &str) -> Interpreter {
+    let program = Parser::new(Scanner::new(code.to_string())).parse_program().unwrap();
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.run(&program);
+    assert!(result.is_ok(), "{:?}", result.err());
+    interpreter
+}
+
+// The elements of a list as they are sent to the frontend.
+fn list_value(interpreter: &Interpreter, name: &str) -> Value {
+    serde_json::to_value(&interpreter.get_declared_lists()[name]).unwrap()
+}
+
+fn run_errors(code: &str) -> Vec<ErrorMessage> {
+    let program = Parser::new(Scanner::new(code.to_string())).parse_program().unwrap();
+    Interpreter::new().run(&program).err().unwrap()
+}
+
+fn variable_value(interpreter: &Interpreter, name: &str) -> String {
+    interpreter.get_declared_variables()[name].1.clone()
+}
+
+#[test]
+fn interpreter_handles_increment_and_compound_assignment() {
+    let interpreter = run("int x = 1;\nx++;\nx += 5;\nx--;\nx -= 2;");
+    assert_eq!(variable_value(&interpreter, "x"), "4");
+    assert_eq!(interpreter.get_declared_variables()["x"].0, TokenType::Int);
+}
+
+#[test]
+fn interpreter_handles_list_declarations() {
+    let interpreter = run("int a[4] = {1, 2, 3};\na[1] = 7;\nint b = a[2] * 2;");
+    assert_eq!(list_value(&interpreter, "a"), json!(["1", "7", "3", "0"]));
+    assert_eq!(variable_value(&interpreter, "b"), "6");
+}
+
+#[test]
+fn interpreter_handles_for_loop_with_increment_operator() {
+    let interpreter = run("int s = 0;\nfor (int i = 0; i < 3; i++) {\n s = s + 2;\n}\nint t = s;");
+    assert_eq!(variable_value(&interpreter, "s"), "6");
+    assert_eq!(variable_value(&interpreter, "t"), "6");
+    // The loop variable only lives as long as the loop
+    assert!(!interpreter.get_declared_variables().contains_key("i"));
+}
+
+#[test]
+fn interpreter_runs_loops_with_bounds_known_only_at_runtime() {
+    let code = "int n = 5; int total = 0; int k = 0;\n\
+        while (k < n * 2) {\n k += 3;\n total = total + k;\n}\n\
+        int d = 10;\ndo {\n d = d / 2;\n} while (d > 1);";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "total"), "30");
+    assert_eq!(variable_value(&interpreter, "d"), "1");
+}
+
+#[test]
+fn interpreter_takes_only_the_selected_branch() {
+    let code = "int x = 3; int y = 0; int z = 0;\n\
+        if (x > 5) {\n y = 1;\n} else if (x > 2) {\n y = 2;\n} else {\n y = 3;\n}\n\
+        switch (x) {\n case 1: z = 10; break;\n case 3: z = 30; break;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "y"), "2");
+    assert_eq!(variable_value(&interpreter, "z"), "30");
+}
+
+#[test]
+fn interpreter_evaluates_operators() {
+    let code = "int a = 7 % 3 + (1 << 4) - (6 & 3) ^ 1;\nbool b = !(a > 3) || a == 14;\n\
+        double c = 1.5 * 2 + 0.25;\nint t = a > 10 ? -a : ~a;\nchar ch = 'a';\nch++;";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "a"), "14");
+    assert_eq!(variable_value(&interpreter, "b"), "true");
+    assert_eq!(variable_value(&interpreter, "c"), "3.25");
+    assert_eq!(variable_value(&interpreter, "t"), "-14");
+    assert_eq!(variable_value(&interpreter, "ch"), "b");
+}
+
+#[test]
+fn interpreter_stores_numeric_and_decoded_literal_values() {
+    let interpreter = run("int h = 0x1F;\nint o = 017;\nfloat f = 3.0f;\ndouble d = 1e-3;\nfloat g = 2.5;\n\
+        char c = '\\x41';\nchar n = '\\n';\nstring s = \"a\\tb\";");
+    assert_eq!(variable_value(&interpreter, "h"), "31");
+    assert_eq!(variable_value(&interpreter, "o"), "15");
+    assert_eq!(variable_value(&interpreter, "f"), "3");
+    assert_eq!(variable_value(&interpreter, "d"), "0.001");
+    assert_eq!(variable_value(&interpreter, "g"), "2.5");
+    assert_eq!(variable_value(&interpreter, "c"), "A");
+    assert_eq!(variable_value(&interpreter, "n"), "\n");
+    assert_eq!(variable_value(&interpreter, "s"), "a\tb");
+}
+
+#[test]
+fn interpreter_reports_errors_and_keeps_going() {
+    let errors = run_errors("int x = y;\nint a[2] = {1, 2};\na[2] = 5;\nint z = 1 / 0;\nint x = 1;\nint x = 2;");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "Use of undeclared variable 'y'",
+        "Index 2 is out of bounds for 'a' of size 2",
+        "Division by zero",
+        "Variable 'x' already declared",
+    ]);
+    assert_eq!((errors[1].span.line, errors[1].span.column), (3, 3));
+}
+
+#[test]
+fn interpreter_stops_programs_that_never_finish() {
+    let errors = run_errors("int x = 0;\nint y = 1;\nwhile (y > 0) {\n x++;\n}");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("Execution stopped after"), "{}", errors[0].message);
+}
+
+#[test]
+fn interpreter_limits_the_memory_and_time_arrays_take() {
+    // Every call keeps its own large array alive
+    let code = "void f() {\n int a[1000000];\n f();\n}\nint main() {\n f();\n return 0;\n}";
+    let errors = run_errors(code);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("Array 'a' does not fit"), "{}", errors[0].message);
+    assert_eq!(errors[0].span.line, 2);
+
+    // Arrays dropped at the end of each iteration give their elements back,
+    // but filling them is charged by size
+    let errors = run_errors("for (int i = 0; i < 1000; i++) {\n int a[1000000];\n}");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("Execution stopped after"), "{}", errors[0].message);
+    let interpreter = run("for (int i = 0; i < 10; i++) {\n int a[1000000];\n}\nint b[1000000];");
+    assert_eq!(list_value(&interpreter, "b")[0], json!("0"));
+}
+
+#[test]
+fn interpreter_handles_strings_chars_and_bools() {
+    let code = "string s = \"ab\" + \"cd\" + '!';\nbool less = \"apple\" < \"banana\";\nbool same = s == \"abcd!\";\n\
+        int i = 'A' + true;\nchar c = 66;\nint t = 3.9;\nbool b = 2;\ndouble d = 'a';";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "s"), "abcd!");
+    assert_eq!(variable_value(&interpreter, "less"), "true");
+    assert_eq!(variable_value(&interpreter, "same"), "true");
+    assert_eq!(variable_value(&interpreter, "i"), "66");
+    assert_eq!(variable_value(&interpreter, "c"), "B");
+    assert_eq!(variable_value(&interpreter, "t"), "3");
+    assert_eq!(variable_value(&interpreter, "b"), "true");
+    assert_eq!(variable_value(&interpreter, "d"), "97");
+
+    let errors = run_errors("string s = \"a\" - \"b\";\nint x = \"a\";");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "Operator '-' cannot be applied to values of type 'String' and 'String'",
+        "Cannot assign a String to a variable of type 'Int'",
+    ]);
+}
+
+#[test]
+fn interpreter_handles_break_and_continue_in_loops() {
+    let code = "int w = 0;\nwhile (w < 100) {\n w++;\n if (w == 4) { break; }\n}\n\
+        int odd = 0;\nfor (int i = 0; i < 10; i++) {\n if (i % 2 == 0) { continue; }\n odd += i;\n}\n\
+        int d = 0; int runs = 0;\ndo {\n d++;\n if (d < 3) { continue; }\n runs++;\n} while (d < 5);";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "w"), "4");
+    assert_eq!(variable_value(&interpreter, "odd"), "25");
+    assert_eq!(variable_value(&interpreter, "d"), "5");
+    assert_eq!(variable_value(&interpreter, "runs"), "3");
+}
+
+#[test]
+fn interpreter_falls_through_switch_cases() {
+    let code = "int x = 1; int hits = 0;\nswitch (x) {\n case 1: hits += 1;\n case 2: hits += 10; break;\n case 3: hits += 100;\n}\n\
+        int skipped = 0;\nfor (int i = 0; i < 4; i++) {\n switch (i) {\n case 1: continue;\n case 2: break;\n }\n skipped += i;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "hits"), "11");
+    assert_eq!(variable_value(&interpreter, "skipped"), "5");
+}
+
+#[test]
+fn interpreter_calls_functions_and_runs_main() {
+    let code = "int calls = 0;\n\
+        int factorial(int n) {\n calls++;\n if (n <= 1) { return 1; }\n return n * factorial(n - 1);\n}\n\
+        double half(double x) {\n return x / 2;\n}\n\
+        void bump(int n) {\n calls += n;\n}\n\
+        int main() {\n int f = factorial(5);\n double h = half(3);\n int n = 7;\n bump(n);\n return 0;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "f"), "120");
+    assert_eq!(variable_value(&interpreter, "h"), "1.5");
+    // The parameter `n` of bump lives in its own frame; main keeps its own `n`
+    assert_eq!(variable_value(&interpreter, "n"), "7");
+    assert_eq!(variable_value(&interpreter, "calls"), "12");
+}
+
+#[test]
+fn interpreter_runs_functions_declared_by_prototypes() {
+    let code = "int odd(int);\nint even(int n) {\n if (n == 0) return 1;\n return odd(n - 1);\n}\n\
+        int odd(int n) {\n if (n == 0) return 0;\n return even(n - 1);\n}\nint main() {\n int e = even(10);\n int o = odd(7);\n return 0;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "e"), "1");
+    assert_eq!(variable_value(&interpreter, "o"), "1");
+}
+
+#[test]
+fn interpreter_checks_calls() {
+    let code = "int f(int a, int b) {\n return a + b;\n}\nvoid g() {\n}\nint h(int a) {\n if (a > 0) { return a; }\n}\n\
+        int r(int a) {\n return r(a + 1);\n}\n\
+        int main() {\n int x = f(1);\n int y = f(1, \"two\");\n int z = g();\n int w = h(0);\n int v = missing(1);\n int u = r(0);\n}";
+    let errors = run_errors(code);
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, ["Function 'f' expects 2 argument(s) but got 1"]);
+
+    let code = "int f(int a, int b) {\n return a + b;\n}\nvoid g() {\n}\nint h(int a) {\n if (a > 0) { return a; }\n}\n\
+        int r(int a) {\n return r(a + 1);\n}\n\
+        int y = f(1, \"two\");\nint z = g();\nint w = h(0);\nint v = missing(1);\nint u = r(0);";
+    let errors = run_errors(code);
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "Argument 2 of 'f' expects type 'Int' but got a String",
+        "Function 'g' does not return a value",
+        "Function 'h' ended without returning a value",
+        "Call to undeclared function 'missing'",
+        "Too many nested calls to 'r'; the recursion may never end",
+    ]);
+    assert_eq!((errors[0].span.line, errors[0].span.column), (12, 14));
+}
+
+#[test]
+fn interpreter_keeps_shadowed_variables_apart() {
+    let code = "int x = 1;\nint seen = 0;\n{\n int x = 10;\n x++;\n seen = x;\n}\n\
+        for (int i = 0; i < 2; i++) {\n x += i;\n}\nfor (int i = 5; i < 6; i++) {\n x += i;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "x"), "7");
+    assert_eq!(variable_value(&interpreter, "seen"), "11");
+}
+
+#[test]
+fn interpreter_zeroes_variables_declared_without_a_value() {
+    let interpreter = run("int x, y = 2;\nfloat f;\nbool b;\nstring s;\nint a[3];\nx = y * 4;");
+    assert_eq!(variable_value(&interpreter, "x"), "8");
+    assert_eq!(variable_value(&interpreter, "y"), "2");
+    assert_eq!(variable_value(&interpreter, "f"), "0");
+    assert_eq!(variable_value(&interpreter, "b"), "false");
+    assert_eq!(variable_value(&interpreter, "s"), "");
+    assert_eq!(list_value(&interpreter, "a"), json!(["0", "0", "0"]));
+}
+
+#[test]
+fn interpreter_stores_arrays_of_any_element_type() {
+    let code = "float a[3] = {1.0, 2.5, 3};\nchar s[] = \"hi\";\nbool flags[2];\nint i = 0;\n\
+        a[i + 1] = a[i] * 4;\na[2] += 0.5;\ns[0]++;\nflags[i + 1] = true;\nfloat last = a[2];";
+    let interpreter = run(code);
+    assert_eq!(list_value(&interpreter, "a"), json!(["1", "4", "3.5"]));
+    assert_eq!(list_value(&interpreter, "s"), json!(["i", "i", "\0"]));
+    assert_eq!(list_value(&interpreter, "flags"), json!(["false", "true"]));
+    assert_eq!(variable_value(&interpreter, "last"), "3.5");
+
+    let errors = run_errors("double d[2];\nint k = 2;\nd[k - 3] = 1;");
+    assert_eq!(errors[0].message, "Index -1 is out of bounds for 'd' of size 2");
+}
+
+#[test]
+fn interpreter_stores_multi_dimensional_arrays_row_major() {
+    let code = "int g[2][3] = {{1, 2}, {4}};\nint total = 0;\nint i = 0;\n\
+        while (i < 2) {\n    g[i][2] = g[i][0] * 10;\n    total += g[i][2];\n    i++;\n}\nchar w[][3] = {\"ab\", \"c\"};\nw[1][1]++;";
+    let interpreter = run(code);
+    assert_eq!(list_value(&interpreter, "g"), json!([["1", "2", "10"], ["4", "0", "40"]]));
+    assert_eq!(list_value(&interpreter, "w"), json!([["a", "b", "\0"], ["c", "\u{1}", "\0"]]));
+    assert_eq!(variable_value(&interpreter, "total"), "50");
+
+    let errors = run_errors("int g[2][3];\nint j = 3;\ng[1][j] = 1;");
+    assert_eq!(errors[0].message, "Index 3 is out of bounds for 'g' of size 3");
+}
+
+#[test]
+fn interpreter_runs_else_if_chains_and_brace_less_bodies() {
+    let code = "int sign(int n) {\n    if (n > 0) return 1;\n    else if (n < 0) return -1;\n    else return 0;\n}\n\
+        int main() {\n    int a = sign(5), b = sign(-3), c = sign(0);\n    int sum = 0;\n\
+        for (int i = 1; i <= 4; i++) sum += i;\n    while (sum > 7) sum--;\n    do sum *= 2; while (sum < 20);\n\
+        int d = 0;\n    if (a > 0) if (b > 0) d = 1; else d = 2;\n    return 0;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "a"), "1");
+    assert_eq!(variable_value(&interpreter, "b"), "-1");
+    assert_eq!(variable_value(&interpreter, "c"), "0");
+    assert_eq!(variable_value(&interpreter, "sum"), "28");
+    // The else belongs to `if (b > 0)`
+    assert_eq!(variable_value(&interpreter, "d"), "2");
+}
+
+#[test]
+fn interpreter_takes_the_default_label_only_when_no_case_matches() {
+    let code = "int trace = 0;\nfor (int i = 0; i < 4; i++) {\n    switch (i) {\n        case 0: trace += 1;\n        default: trace += 10;\n        case 2: trace += 100; break;\n        case 3: continue;\n    }\n    trace += 1000;\n}\n\
+        int found = -1;\nint j = 0;\nwhile (j < 10) {\n    switch (j) { case 2: found = j; break; }\n    if (found >= 0) break;\n    j++;\n}";
+    let interpreter = run(code);
+    // 0 falls through default into case 2, 1 starts at default, 2 only runs case 2 and 3 continues the loop
+    assert_eq!(variable_value(&interpreter, "trace"), "3321");
+    assert_eq!(variable_value(&interpreter, "found"), "2");
+    assert_eq!(variable_value(&interpreter, "j"), "2");
+}
+
+#[test]
+fn interpreter_declares_what_the_skipped_cases_of_a_switch_declare() {
+    let code = "int seen = 0;\nfor (int i = 1; i <= 2; i++) {\n    switch (i) {\n        case 1: int z = 5; int a[2] = {1, 2}; break;\n        case 2: seen = z + a[0] + a[1]; z = 3; seen += z;\n    }\n}";
+    // Resolving and checking accept the program, and running it agrees
+    let mut program = Parser::new(Scanner::new(code.to_string())).parse_program().unwrap();
+    assert!(resolve(&program).1.is_empty());
+    assert!(check(&mut program).is_empty());
+    let interpreter = run(code);
+    // Jumping to `case 2` skips the initializers, so `z` and `a` start out as zero
+    assert_eq!(variable_value(&interpreter, "seen"), "3");
+}
+
+#[test]
+fn interpreter_runs_for_loops_over_any_variable_with_runtime_bounds() {
+    let code = "int limit(int n) {\n    return n * 2;\n}\nvoid tick() {\n}\n\
+        int main() {\n    int steps = 0, pairs = 0, forever = 0;\n\
+        for (int row = 0; row < limit(2); row++)\n        for (int col = row; col < 4; col++) steps++;\n\
+        int lo, hi;\n    for (lo = 0, hi = 9; lo < hi; lo++, hi--, tick()) pairs++;\n\
+        for (;;) {\n        forever++;\n        if (forever == 5) break;\n    }\n\
+        int last = (lo, hi + 100);\n    return 0;\n}";
+    let interpreter = run(code);
+    assert_eq!(variable_value(&interpreter, "steps"), "10");
+    assert_eq!(variable_value(&interpreter, "pairs"), "5");
+    assert_eq!(variable_value(&interpreter, "lo"), "5");
+    assert_eq!(variable_value(&interpreter, "forever"), "5");
+    assert_eq!(variable_value(&interpreter, "last"), "104");
+}
@@ -1,2 +1,8 @@
+mod checker_tests;
+mod incremental_tests;
+mod interpreter_tests;
+mod parser_tests;
+mod preprocessor_tests;
+mod scanner_bench;
 mod scanner_tests;
-mod scanner;
\ No newline at end of file
+mod symbols_tests;
@@ -0,0 +1,464 @@
+use crate::interpreter::with_large_stack;
+use crate::parser::{ErrorMessage, ExprNode, Parser, ProgramNode, StmtNode};
+use crate::scanner::Scanner;
+use crate::token::{Span, TokenType};
+
+fn parse(code: &str) -> ProgramNode {
+    let result = Parser::new(Scanner::new(code.to_string())).parse_program();
+    assert!(result.is_ok(), "{:?}", result.err());
+    result.unwrap()
+}
+
+fn parse_errors(code: &str) -> Vec<ErrorMessage> {
+    Parser::new(Scanner::new(code.to_string())).parse_program().err().unwrap()
+}
+
+fn parse_warnings(code: &str) -> Vec<ErrorMessage> {
+    let mut parser = Parser::new(Scanner::new(code.to_string()));
+    let result = parser.parse_program();
+    assert!(result.is_ok(), "{:?}", result.err());
+    parser.warnings().to_vec()
+}
+
+fn declared_expressions(code: &str) -> Vec<String> {
+    parse(code).statements.iter()
+        .filter_map(|statement| match &statement.node {
+            StmtNode::Declaration(_, _, Some(expr)) => Some(expr.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn parser_errors_cover_the_offending_token() {
+    let errors = parse_errors("int x = 1;\nint 42 = 3;");
+    assert_eq!(errors[0].message, "Expected a variable");
+    assert_eq!(errors[0].span, Span { start: 15, end: 17, line: 2, column: 5, end_line: 2, end_column: 7 });
+}
+
+#[test]
+fn parser_types_numeric_literals() {
+    let program = parse("int h = 0x1F;\nfloat f = 3.0f;\ndouble d = 1e-3;");
+    let values: Vec<&ExprNode> = program.statements.iter()
+        .filter_map(|statement| match &statement.node {
+            StmtNode::Declaration(_, _, Some(expr)) => Some(&expr.node),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(values, [&ExprNode::IntLiteral(31), &ExprNode::FloatLiteral(3.0), &ExprNode::DoubleLiteral(0.001)]);
+}
+
+#[test]
+fn parser_reports_integer_literal_overflow() {
+    let errors = parse_errors("int x = 2147483648;\nint y = 4294967295u;");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].message, "Integer literal '2147483648' has type 'long' and does not fit in type 'int'");
+    assert_eq!(errors[0].span.line, 1);
+    assert_eq!(errors[1].message, "Integer literal '4294967295u' has type 'unsigned int' and does not fit in type 'int'");
+    assert_eq!(errors[1].span.line, 2);
+}
+
+#[test]
+fn parser_types_integer_literals_like_c() {
+    // Hex and octal literals try the unsigned type before the next wider one
+    let cases = [
+        ("0xFFFFFFFF", "unsigned int"),
+        ("037777777777", "unsigned int"),
+        ("4294967295", "long"),
+        ("0x100000000", "long"),
+        ("2147483648LL", "long long"),
+        ("0xFFFFFFFFFFFFFFFF", "unsigned long"),
+        ("2147483648ul", "unsigned long"),
+    ];
+    for (literal, type_name) in cases {
+        let errors = parse_errors(&format!("int x = {};", literal));
+        assert_eq!(errors[0].message, format!("Integer literal '{}' has type '{}' and does not fit in type 'int'", literal, type_name));
+    }
+    let errors = parse_errors("int x = 18446744073709551615;");
+    assert_eq!(errors[0].message, "Integer literal '18446744073709551615' is too large for any integer type");
+    // Suffixed values int can hold are read as they are
+    let program = parse("int x = 7u;\nint y = 0x7FFFFFFFL;");
+    assert!(matches!(&program.statements[1].node, StmtNode::Declaration(_, _, Some(expr)) if expr.node == ExprNode::IntLiteral(i32::MAX)));
+}
+
+#[test]
+fn parser_pulls_tokens_from_the_scanner_and_reports_lexical_errors() {
+    let errors = parse_errors("int x = 1;\nint y = 2 $;");
+    assert_eq!(errors[0].message, "Unexpected character '$'");
+    assert_eq!(errors[0].span.line, 2);
+}
+
+#[test]
+fn parser_respects_operator_precedence() {
+    let code = "int a = 1; int b = 2; int c = 3;\n\
+        int x = a + b * c - a % b;\n\
+        int y = a << b + c;\n\
+        bool z = a < b == b > c && a | b ^ c & a || !c;\n\
+        int t = a > b ? a : b > c ? b : c;\n\
+        int u = -~a * (b + c);";
+    assert_eq!(declared_expressions(code)[3..], [
+        "((a + (b * c)) - (a % b))",
+        "(a << (b + c))",
+        "((((a < b) == (b > c)) && (a | (b ^ (c & a)))) || !c)",
+        "((a > b) ? a : ((b > c) ? b : c))",
+        "(-~a * (b + c))",
+    ]);
+}
+
+#[test]
+fn parser_stops_at_deeply_nested_expressions() {
+    let parentheses = format!("int x = {}1{};", "(".repeat(100_000), ")".repeat(100_000));
+    let negations = format!("int y = {}1;", "-".repeat(100_000));
+    let assignments = format!("int z; z = {}1;", "z = ".repeat(100_000));
+    // Left associative operators and indexing nest without recursing
+    let sums = format!("int s = 1{};", " + 1".repeat(100_000));
+    let indexes = format!("int i = a{};", "[0]".repeat(100_000));
+    for code in [parentheses, negations, assignments, sums, indexes] {
+        // Submitted programs are parsed on the interpreter's stack as well
+        let errors = with_large_stack(|| parse_errors(&code));
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(messages, ["Nesting is too deep"]);
+    }
+    // Nesting within the limit still parses
+    let code = format!("int x = {}1{};", "(".repeat(200), ")".repeat(200));
+    assert_eq!(with_large_stack(|| declared_expressions(&code)), ["1"]);
+}
+
+#[test]
+fn parser_stops_at_deeply_nested_statements() {
+    let blocks = format!("int main() {}", "{".repeat(3000));
+    let conditions = format!("int x = 1;\n{}x++;", "if (x) ".repeat(1000));
+    let initializer = format!("int a{} = {}1{};", "[1]".repeat(300), "{".repeat(300), "}".repeat(300));
+    for code in [blocks, conditions, initializer] {
+        let errors = with_large_stack(|| parse_errors(&code));
+        // Recovery picks up again after the statement that went too deep
+        assert_eq!(errors[0].message, "Nesting is too deep");
+        assert!(errors.len() < 10, "{:?}", errors);
+    }
+}
+
+#[test]
+fn parser_makes_assignment_right_associative() {
+    let code = "int a = 1; int b = 2;\nint c = a = b <<= 3;";
+    assert_eq!(declared_expressions(code)[2], "(a = (b <<= 3))");
+    let errors = parse_errors("int a = 1;\nint b = a + 1 = 2;");
+    assert_eq!(errors[0].message, "Left side of an assignment must be a variable");
+    assert_eq!((errors[0].span.line, errors[0].span.column), (2, 15));
+}
+
+#[test]
+fn parser_builds_a_loop_once_without_running_it() {
+    let program = parse("int n = 0;\nfor (int i = 0; i < n; i++) {\n n += i;\n}");
+    let StmtNode::ForLoop(init, condition, increment, body) = &program.statements[1].node else {
+        panic!("expected a for loop, got {:?}", program.statements[1]);
+    };
+    assert!(matches!(init.as_deref().map(|init| &init.node), Some(StmtNode::Declaration(TokenType::Int, name, _)) if name == "i"));
+    assert_eq!(condition.as_ref().unwrap().to_string(), "(i < n)");
+    assert_eq!(increment.as_ref().unwrap().to_string(), "i++");
+    assert!(matches!(&body.node, StmtNode::Block(statements) if statements.len() == 1));
+    // Statement spans run from the first token to the last
+    assert_eq!((program.statements[1].span.line, program.statements[1].span.end_line), (2, 4));
+}
+
+#[test]
+fn parser_warns_about_constant_conditions() {
+    let errors = parse_warnings("int x = 0;\nwhile (1) {\n x++;\n}");
+    assert_eq!((errors[0].message_type.as_str(), errors[0].message.as_str()), ("Warning", "Warning: This condition is always true"));
+    assert_eq!(errors[0].span.line, 2);
+}
+
+#[test]
+fn parser_accepts_programs_with_constant_conditions() {
+    // `while (1)` with a `break` is a normal loop, so the warnings do not stop the program
+    let warnings: Vec<(String, usize)> = parse_warnings("int x = 0;\nwhile (1) {\n x++;\n}\ndo {\n} while (false);").into_iter()
+        .map(|warning| (warning.message, warning.span.line))
+        .collect();
+    assert_eq!(warnings, [
+        ("Warning: This condition is always true".to_string(), 2),
+        ("Warning: This condition is always false".to_string(), 6),
+    ]);
+}
+
+#[test]
+fn parser_builds_function_definitions_and_calls() {
+    let program = parse("int add(int a, float b) {\n return a + b;\n}\nvoid log(void) {\n return;\n}\nint main() {\n int x = add(1, 2.5f) * 2;\n log();\n}");
+    let StmtNode::Function(return_type, name, parameters, body) = &program.statements[0].node else {
+        panic!("expected a function, got {:?}", program.statements[0].node);
+    };
+    assert_eq!((return_type, name.as_str()), (&TokenType::Int, "add"));
+    assert_eq!(parameters, &[(TokenType::Int, "a".to_string()), (TokenType::Float, "b".to_string())]);
+    assert!(matches!(&body.node, StmtNode::Block(statements) if matches!(statements[0].node, StmtNode::Return(Some(_)))));
+    assert!(matches!(&program.statements[1].node, StmtNode::Function(TokenType::Void, _, parameters, _) if parameters.is_empty()));
+
+    let StmtNode::Function(_, _, _, body) = &program.statements[2].node else { panic!() };
+    let StmtNode::Block(statements) = &body.node else { panic!() };
+    assert!(matches!(&statements[0].node, StmtNode::Declaration(_, _, Some(expr)) if expr.to_string() == "(add(1, 2.5) * 2)"));
+
+    // Arguments are assignment expressions, still separated by commas
+    assert_eq!(declared_expressions("int r, s;\nint t = g(r = 1, s += 2);"), ["g((r = 1), (s += 2))"]);
+}
+
+#[test]
+fn parser_reads_function_prototypes() {
+    let program = parse("int f(int);\nvoid g(int x, char);\ndouble h(void);");
+    let prototypes: Vec<&StmtNode> = program.statements.iter().map(|statement| &statement.node).collect();
+    assert_eq!(prototypes, [
+        &StmtNode::Prototype(TokenType::Int, "f".to_string(), vec![TokenType::Int]),
+        &StmtNode::Prototype(TokenType::Void, "g".to_string(), vec![TokenType::Int, TokenType::Char]),
+        &StmtNode::Prototype(TokenType::Double, "h".to_string(), vec![]),
+    ]);
+    // A definition still names every parameter
+    let errors = parse_errors("int f(int) {\n return 1;\n}");
+    assert_eq!((errors[0].message.as_str(), errors[0].span.column), ("Expected a parameter name", 7));
+}
+
+#[test]
+fn parser_checks_returns_and_function_placement() {
+    let errors = parse_errors("return 1;\nvoid f() {\n return 2;\n}\nint g() {\n return;\n}\nint h() { int k() {} }\nint y = 3(4);");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "'return' outside of a function",
+        "Void function 'f' should not return a value",
+        "Non-void function 'g' should return a value",
+        "Functions can only be defined at the top level",
+        "Only functions can be called",
+    ]);
+}
+
+#[test]
+fn parser_accepts_declarator_lists_without_initializers() {
+    let program = parse("int x;\nint a = 1, b, c[2], d[] = {4, 5};\nfor (int i = 0, j = 3; i < j; i++) {\n}");
+    assert!(matches!(&program.statements[0].node, StmtNode::Declaration(TokenType::Int, name, None) if name == "x"));
+
+    let StmtNode::Declarations(declarations) = &program.statements[1].node else {
+        panic!("expected several declarations, got {:?}", program.statements[1].node);
+    };
+    assert!(matches!(&declarations[0].node, StmtNode::Declaration(_, name, Some(_)) if name == "a"));
+    assert!(matches!(&declarations[1].node, StmtNode::Declaration(_, name, None) if name == "b"));
+    assert!(matches!(&declarations[2].node, StmtNode::ListDeclaration(_, name, dimensions, values) if name == "c" && *dimensions == [2] && values.is_empty()));
+    assert!(matches!(&declarations[3].node, StmtNode::ListDeclaration(_, name, dimensions, values) if name == "d" && *dimensions == [2] && values.len() == 2));
+    // Later declarators start at their own name
+    assert_eq!((declarations[1].span.column, declarations[1].span.end_column), (12, 13));
+
+    let StmtNode::ForLoop(Some(init), ..) = &program.statements[2].node else { panic!() };
+    assert!(matches!(&init.node, StmtNode::Declarations(declarations) if declarations.len() == 2));
+
+    let errors = parse_errors("int e[];\nint f 5;");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, ["Array size missing in 'e'", "Expected a semicolon"]);
+}
+
+#[test]
+fn parser_reads_array_initializers_of_any_element_type() {
+    let program = parse("float a[3] = {1.0, 2.5, 3};\nchar s[] = \"hi\";\nchar t[2] = \"hi\";\nchar u[4] = \"hi\";");
+    let lengths: Vec<(usize, usize)> = program.statements.iter()
+        .map(|statement| match &statement.node {
+            StmtNode::ListDeclaration(_, _, dimensions, values) => (dimensions[0], values.len()),
+            node => panic!("expected a list declaration, got {:?}", node),
+        })
+        .collect();
+    // The terminating '\0' is kept only when there is room for it
+    assert_eq!(lengths, [(3, 3), (3, 3), (2, 2), (4, 3)]);
+    let StmtNode::ListDeclaration(TokenType::Char, _, _, values) = &program.statements[1].node else { panic!() };
+    assert_eq!((values[2].0, &values[2].1.node), (2, &ExprNode::CharLiteral('\0')));
+
+    let errors = parse_errors("int n[] = \"no\";\nint z[0];\nchar c[1] = \"ab\";");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "Only char arrays can be initialized from a string",
+        "Array 'z' must have a positive size",
+        "Too many values in list initialization",
+    ]);
+}
+
+#[test]
+fn parser_lays_out_nested_initializers_in_row_major_order() {
+    let program = parse("int g[2][3] = {{1, 2}, {4}};\nint h[][2] = {1, 2, 3};\nchar w[][3] = {\"ab\", \"c\"};\nint k[2][2][2] = {{{1}, {2, 3}}, 4};");
+    let layouts: Vec<(Vec<usize>, Vec<usize>)> = program.statements.iter()
+        .map(|statement| match &statement.node {
+            StmtNode::ListDeclaration(_, _, dimensions, values) => {
+                (dimensions.clone(), values.iter().map(|(position, _)| *position).collect())
+            },
+            node => panic!("expected a list declaration, got {:?}", node),
+        })
+        .collect();
+    assert_eq!(layouts, [
+        (vec![2, 3], vec![0, 1, 3]),
+        // Without braces the values fill the rows in turn
+        (vec![2, 2], vec![0, 1, 2]),
+        (vec![2, 3], vec![0, 1, 2, 3, 4]),
+        (vec![2, 2, 2], vec![0, 2, 3, 4]),
+    ]);
+    // Elements are assignment expressions
+    let program = parse("int b;\nint arr[2] = {b = 1, 2};");
+    let StmtNode::ListDeclaration(_, _, _, values) = &program.statements[1].node else { panic!() };
+    assert_eq!(values[0].1.to_string(), "(b = 1)");
+
+    let errors = parse_errors("int a[2][2] = {{1, 2, 3}};\nint b[2][2] = {{1}, {2}, {3}};\nint c[2] = {{1}, 2};\nint d[][] = {1};\nint e[2][2] = {1, 2, 3, 4, 5};");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "Too many values in list initialization",
+        "Too many values in list initialization",
+        "Expected a value instead of '{'",
+        "Only the first dimension of 'd' may be omitted",
+        "Too many values in list initialization",
+    ]);
+    assert_eq!((errors[0].span.line, errors[0].span.column), (1, 23));
+}
+
+#[test]
+fn parser_recovers_at_statement_boundaries() {
+    // Two mistakes on one line are both reported
+    let errors = parse_errors("int a = ; int b = * 2;\nint c = 1;");
+    let positions: Vec<(usize, usize)> = errors.iter().map(|error| (error.span.line, error.span.column)).collect();
+    assert_eq!(positions, [(1, 9), (1, 19)]);
+
+    // A mistake inside a block leaves the rest of the block and program intact
+    let code = "int main() {\n    int x = 1\n    if (x > 0) {\n        x = ;\n    }\n    int a[2] = {1, 2, 3};\n    x = 2;\n}\nint y = ;";
+    let errors = parse_errors(code);
+    let found: Vec<(&str, usize)> = errors.iter().map(|error| (error.message.as_str(), error.span.line)).collect();
+    assert_eq!(found, [
+        ("Expected a semicolon", 3),
+        ("Expected a number, variable, or expression", 4),
+        ("Too many values in list initialization", 6),
+        ("Expected a number, variable, or expression", 9),
+    ]);
+
+    // A bad case label stays inside the switch
+    for code in [
+        "int main() { int x = 1; int y = 0; switch (x) { case : y = 1; break; case 2: y = 2; break; } y = 3; return 0; }",
+        "int main() { int x = 1; int y = 0; switch (x) { case 1 y = 1; break; case 2: y = 2; break; } y = 3; return 0; }",
+    ] {
+        let errors = parse_errors(code);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+    }
+}
+
+#[test]
+fn parser_recovers_inside_loop_headers() {
+    let code = "int main() {\n    int y = 0;\n    while (y < ) {\n        y = ;\n    }\n    for (int i = 0; i < 3 i++) {\n        y += ;\n    }\n    switch () {\n        case 1: y = ;\n    }\n}";
+    let errors = parse_errors(code);
+    let lines: Vec<usize> = errors.iter().map(|error| error.span.line).collect();
+    assert_eq!(lines, [3, 4, 6, 7, 9, 10]);
+    assert_eq!(errors[2].message, "Expected ';'");
+}
+
+#[test]
+fn parser_stops_after_too_many_errors() {
+    let errors = parse_errors(&"int x = ;\n".repeat(40));
+    assert_eq!(errors.len(), 26);
+    assert_eq!(errors[25].message, "Too many errors; the rest of the program was not checked");
+    assert_eq!(errors[25].span.line, 25);
+}
+
+#[test]
+fn parser_caps_lexical_and_syntax_errors_together() {
+    // Odd lines hold a syntax error and even lines a lexical one
+    let errors = parse_errors(&"int x = ;\nint y = 1 @;\n".repeat(20));
+    assert_eq!(errors.len(), 26);
+    let lines: Vec<usize> = errors[..25].iter().map(|error| error.span.line).collect();
+    assert_eq!(lines, (1..=25).collect::<Vec<_>>());
+    assert_eq!(errors[1].message, "Unexpected character '@'");
+    assert_eq!(errors[25].message, "Too many errors; the rest of the program was not checked");
+    assert_eq!(errors[25].span.line, 25);
+
+    let errors = parse_errors(&"@\n".repeat(40));
+    assert_eq!(errors.len(), 26);
+    assert_eq!(errors[25].message, "Too many errors; the rest of the program was not checked");
+}
+
+#[test]
+fn parser_reports_unexpected_end_of_input() {
+    let cases = [
+        ("int", "Unexpected end of input, expected a variable", 4),
+        ("x[", "Unexpected end of input, expected a number, variable, or expression", 3),
+        ("int x = 1", "Unexpected end of input, expected a semicolon", 10),
+        ("int main() {", "Unexpected end of input, expected '}'", 13),
+        ("int a[2] = {1", "Unexpected end of input, expected '}'", 14),
+        ("void f(int", "Unexpected end of input, expected ')'", 11),
+    ];
+    for (code, message, column) in cases {
+        let errors = parse_errors(code);
+        assert_eq!(errors[0].message, message, "{}", code);
+        assert_eq!((errors[0].span.line, errors[0].span.column), (1, column), "{}", code);
+    }
+
+    for code in ["", "   \n\t", "// nothing here\n"] {
+        assert!(parse(code).statements.is_empty());
+    }
+}
+
+#[test]
+fn parser_never_panics_on_a_truncated_program() {
+    let code = "int g[2][2] = {{1, 2}, {3}};\nchar s[] = \"hi\";\n\
+        int add(int a, int b) {\n    return a + b;\n}\n\
+        int main() {\n    int total = 0;\n    for (int i = 0; i < 2; i++) {\n        total += add(g[i][0], -g[i][1]);\n    }\n\
+        switch (total) {\n        case 1: total++; break;\n    }\n    do { total--; } while (total > 0 && s[0] != 'x');\n    return total ? 1 : 0;\n}\n";
+    for end in (0..code.len()).filter(|end| code.is_char_boundary(*end)) {
+        // Only the result matters here, not which errors a prefix has
+        let _ = Parser::new(Scanner::new(code[..end].to_string())).parse_program();
+    }
+    parse(code);
+}
+
+#[test]
+fn parser_reads_else_if_chains_and_brace_less_bodies() {
+    let program = parse("int x = 0;\nif (x > 1) x = 1; else if (x < 0) x = 2; else { x = 3; }\nif (x > 0) if (x > 2) x--; else x++;\nwhile (x < 9) x++;\nfor (int i = 0; i < 2; i++) x += i;\ndo x--; while (x > 5);");
+
+    // Each `else if` is an if-statement in the else branch of the one before
+    let StmtNode::IfStatement(_, then_branch, Some(else_branch)) = &program.statements[1].node else { panic!() };
+    assert!(matches!(then_branch.node, StmtNode::Expression(_)));
+    let StmtNode::IfStatement(_, _, Some(last)) = &else_branch.node else { panic!("expected else if, got {:?}", else_branch.node) };
+    assert!(matches!(last.node, StmtNode::Block(_)));
+
+    // The dangling else goes with the inner if
+    let StmtNode::IfStatement(_, inner, None) = &program.statements[2].node else { panic!() };
+    assert!(matches!(&inner.node, StmtNode::IfStatement(_, _, Some(_))));
+
+    assert!(matches!(&program.statements[3].node, StmtNode::WhileLoop(_, body) if matches!(body.node, StmtNode::Expression(_))));
+    assert!(matches!(&program.statements[4].node, StmtNode::ForLoop(.., body) if matches!(body.node, StmtNode::Expression(_))));
+    assert!(matches!(&program.statements[5].node, StmtNode::DoWhileLoop(body, _) if matches!(body.node, StmtNode::Expression(_))));
+
+    let errors = parse_errors("int x = 0;\nif (x > 0) int y = 1;\nelse x = 2;\nwhile (x < 3) x++;");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, ["A declaration cannot be the body of 'if' without braces", "'else' without a matching 'if'"]);
+}
+
+#[test]
+fn parser_checks_where_break_and_continue_appear() {
+    let program = parse("int x = 0;\nswitch (x) {\n    case 1: x = 1;\n    default: x = 2; break;\n    case 2: x = 3;\n}\nwhile (x < 3) {\n    switch (x) { case 0: continue; }\n    break;\n}");
+    let StmtNode::SwitchCase(_, cases) = &program.statements[1].node else { panic!() };
+    let labels: Vec<Option<String>> = cases.iter().map(|(label, _)| label.as_ref().map(|label| label.node.to_string())).collect();
+    assert_eq!(labels, [Some("1".to_string()), None, Some("2".to_string())]);
+    assert!(matches!(cases[1].1[1].node, StmtNode::Break));
+
+    let code = "int x = 0;\nbreak;\nif (x > 0) { continue; }\nswitch (x) { case 1: continue; default: break; default: x = 1; }\ncase 2: x = 2;";
+    let errors = parse_errors(code);
+    let found: Vec<(&str, usize)> = errors.iter().map(|error| (error.message.as_str(), error.span.line)).collect();
+    assert_eq!(found, [
+        ("'break' outside of a loop or switch", 2),
+        ("'continue' outside of a loop", 3),
+        ("'continue' outside of a loop", 4),
+        ("Multiple default labels in one switch", 4),
+        ("'case' outside of a switch", 5),
+    ]);
+}
+
+#[test]
+fn parser_reads_for_loops_with_any_clauses() {
+    let program = parse("int a = 0, b = 9;\nfor (;;) break;\nfor (a = 0, b = 9; a < b; a++, b--) {\n}\nfor (; a < 3;) a++;");
+    assert_eq!(program.statements.len(), 4);
+    assert!(matches!(&program.statements[1].node, StmtNode::ForLoop(None, None, None, _)));
+
+    let StmtNode::ForLoop(Some(init), Some(condition), Some(increment), _) = &program.statements[2].node else { panic!() };
+    let StmtNode::Expression(init) = &init.node else { panic!() };
+    assert_eq!(init.to_string(), "((a = 0), (b = 9))");
+    assert_eq!(condition.to_string(), "(a < b)");
+    assert_eq!(increment.to_string(), "(a++, b--)");
+
+    let StmtNode::ForLoop(None, Some(condition), None, _) = &program.statements[3].node else { panic!() };
+    assert_eq!(condition.to_string(), "(a < 3)");
+    // A comma only joins expressions where it does not separate arguments
+    assert_eq!(declared_expressions("int a, b;\nint c = (a, b);\nint d = sum(a, b);"), ["(a, b)", "sum(a, b)"]);
+}
+
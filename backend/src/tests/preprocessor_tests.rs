@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::preprocessor::preprocess;
+use crate::scanner::ScannerOptions;
+use crate::token::{Span, Token, TokenGlobal};
+
+fn files(entries: &[(&str, &str)]) -> HashMap<String, String> {
+    entries.iter().map(|(name, source)| (name.to_string(), source.to_string())).collect()
+}
+
+fn tokens(code: &str, files: &HashMap<String, String>) -> Vec<Token> {
+    preprocess(code, files).tokens(ScannerOptions::default()).collect()
+}
+
+fn lexemes(code: &str, files: &HashMap<String, String>) -> Vec<String> {
+    tokens(code, files).into_iter()
+        .filter(|token| token.token_global != TokenGlobal::Error)
+        .map(|token| token.lexeme)
+        .collect()
+}
+
+fn diagnostics(code: &str, files: &HashMap<String, String>) -> Vec<(String, Span)> {
+    tokens(code, files).into_iter()
+        .filter_map(|token| token.diagnostic.map(|diagnostic| (diagnostic, token.span)))
+        .collect()
+}
+
+#[test]
+fn preprocessor_expands_object_and_function_like_macros() {
+    let code = "#define N 10 // size\n#define MAX(a, b) ((a) > (b) ? (a) : (b))\nint m = MAX(N, 2) + N0;";
+    assert_eq!(lexemes(code, &HashMap::new()).join(" "), "int m = ( ( 10 ) > ( 2 ) ? ( 10 ) : ( 2 ) ) + N0 ;");
+    // Self-reference stops after one step and names inside literals are left alone
+    let code = "#define X X + 1\n#define S oops\nint y = X; string s = \"S\";";
+    assert_eq!(lexemes(code, &HashMap::new()).join(" "), "int y = X + 1 ; string s = \"S\" ;");
+}
+
+#[test]
+fn preprocessor_handles_conditional_compilation() {
+    let code = "#define DEBUG\n#ifdef DEBUG\nint a;\n#ifndef DEBUG\nint b;\n#else\nint c;\n#endif\n#else\nint d;\n#endif\n#undef DEBUG\n#ifdef DEBUG\nint e;\n#endif";
+    assert_eq!(lexemes(code, &HashMap::new()).join(" "), "int a ; int c ;");
+}
+
+#[test]
+fn preprocessor_includes_files_from_the_virtual_file_set() {
+    let set = files(&[
+        ("config.h", "#ifndef CONFIG_H\n#define CONFIG_H\n#define SIZE 3\n#endif\n"),
+        ("a.h", "#include \"b.h\"\n"),
+        ("b.h", "#include \"a.h\"\n"),
+    ]);
+    let code = "#include <stdio.h>\n#include \"config.h\"\n#include \"config.h\"\nint x = SIZE;";
+    assert_eq!(lexemes(code, &set).join(" "), "int x = 3 ;");
+    assert_eq!(diagnostics("#include \"missing.h\"\n#include \"a.h\"", &set), vec![
+        ("Cannot find include file 'missing.h'".to_string(), Span { start: 0, end: 20, line: 1, column: 1, end_line: 1, end_column: 21 }),
+        ("In included file 'b.h': Recursive include of 'a.h'".to_string(), Span { start: 21, end: 35, line: 2, column: 1, end_line: 2, end_column: 15 }),
+    ]);
+}
+
+#[test]
+fn preprocessor_reports_malformed_directives() {
+    let code = "#define MAX(a, b) a\n#ifdef\n#endif\n#pragma once\nint x = MAX(1);\n#ifndef X\n";
+    let messages: Vec<String> = diagnostics(code, &HashMap::new()).into_iter().map(|(message, _)| message).collect();
+    assert_eq!(messages, vec![
+        "Expected a macro name after '#ifdef'",
+        "Unknown preprocessor directive '#pragma'",
+        "Macro 'MAX' expects 2 arguments but got 1",
+        "Unterminated '#ifndef'",
+    ]);
+}
+
+#[test]
+fn preprocessor_maps_spans_back_to_the_original_file() {
+    let code = "#define N 10\n#define ONE 1\nint x = N; @";
+    let tokens = tokens(code, &HashMap::new());
+    // The expansion covers the macro name, the rest keeps its own position
+    let ten = tokens.iter().find(|token| token.lexeme == "10").unwrap();
+    assert_eq!((ten.span.line, ten.span.column, ten.span.end_column), (3, 9, 10));
+    assert_eq!(diagnostics(code, &HashMap::new()), vec![
+        ("Unexpected character '@'".to_string(), Span { start: 38, end: 39, line: 3, column: 12, end_line: 3, end_column: 13 }),
+    ]);
+}
+
+#[test]
+fn preprocessor_collects_macro_arguments_across_lines() {
+    let code = "#define F(a, b) a + b\nint r = F(1, // first\n2);\nint s = r;";
+    assert_eq!(lexemes(code, &HashMap::new()).join(" "), "int r = 1 + 2 ; int s = r ;");
+    let tokens = tokens(code, &HashMap::new());
+    // The expansion covers the whole invocation and later lines keep their positions
+    let two = tokens.iter().find(|token| token.lexeme == "2").unwrap();
+    assert_eq!((two.span.line, two.span.column, two.span.end_line, two.span.end_column), (2, 9, 3, 3));
+    let s = tokens.iter().find(|token| token.lexeme == "s").unwrap();
+    assert_eq!((s.span.line, s.span.column), (4, 5));
+    // Collection stops at a directive or the end of the file
+    let messages: Vec<String> = diagnostics("#define F(a) a\nint r = F(1,\n#define G\nint t = F(2", &HashMap::new())
+        .into_iter().map(|(message, _)| message).collect();
+    assert_eq!(messages, vec![
+        "Unterminated argument list for macro 'F'",
+        "Unterminated argument list for macro 'F'",
+    ]);
+}
+
+#[test]
+fn preprocessor_splices_continued_directive_lines() {
+    let code = "#define MAX(a, b) \\\n    ((a) > (b) \\\n    ? (a) : (b))\nint m = MAX(1, 2); @";
+    assert_eq!(lexemes(code, &HashMap::new()).join(" "), "int m = ( ( 1 ) > ( 2 ) ? ( 1 ) : ( 2 ) ) ;");
+    // The continued lines stay in the map, so what follows keeps its position
+    let m = tokens(code, &HashMap::new()).into_iter().find(|token| token.lexeme == "m").unwrap();
+    assert_eq!((m.span.line, m.span.column), (4, 5));
+    let (message, span) = diagnostics(code, &HashMap::new()).remove(0);
+    assert_eq!((message.as_str(), span.line, span.column), ("Unexpected character '@'", 4, 20));
+}
+
+#[test]
+fn preprocessed_tokens_feed_the_parser() {
+    let set = files(&[("sizes.h", "#define SIZE 4\n")]);
+    let code = "#include \"sizes.h\"\n#define ID(v) v\nint x = ID(SIZE);";
+    let program = Parser::new(preprocess(code, &set).tokens(ScannerOptions::default())).parse_program().unwrap();
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.run(&program).is_ok());
+    assert_eq!(interpreter.get_declared_variables()["x"].1, "4");
+}
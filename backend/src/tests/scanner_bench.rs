@@ -0,0 +1,34 @@
+// Throughput check for the scanner on generated multi-thousand-line programs.
+// Run with `cargo test --release scanner_bench -- --ignored --nocapture`.
+use std::time::{Duration, Instant};
+use crate::scanner::Scanner;
+
+fn generate_program(lines: usize) -> String {
+    (0..lines)
+        .map(|i| format!("int value_{i} = {i} + 0x1F * (other_{i} << 2); // line {i}\n"))
+        .collect()
+}
+
+fn time_scan(code: &str) -> Duration {
+    let start = Instant::now();
+    let tokens = Scanner::new(code.to_string()).scan();
+    assert!(!tokens.tokens.is_empty());
+    start.elapsed()
+}
+
+#[test]
+#[ignore]
+fn scanner_bench_scales_linearly() {
+    let mut per_line = Vec::new();
+    for lines in [1_000, 2_000, 4_000, 8_000, 16_000] {
+        let code = generate_program(lines);
+        time_scan(&code); // warm up
+        let best = (0..5).map(|_| time_scan(&code)).min().unwrap();
+        let nanos_per_line = best.as_nanos() as f64 / lines as f64;
+        println!("{:>6} lines: {:>10.3?} ({:.0} ns/line)", lines, best, nanos_per_line);
+        per_line.push(nanos_per_line);
+    }
+    // Quadratic behaviour would grow the per-line cost 16x across this range
+    let growth = per_line.last().unwrap() / per_line.first().unwrap();
+    assert!(growth < 3.0, "per-line scan cost grew {:.1}x", growth);
+}
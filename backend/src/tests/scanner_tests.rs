@@ -1,57 +1,348 @@
-use crate::Scanner;
-use std::collections::HashSet;
+use crate::keywords::{Dialect, LanguageConfig};
+use crate::scanner::{analyze, scanning_input_code, Code, Scanner, ScannerOptions, TokenStream};
+use crate::token::{LiteralValue, Span, Token, TokenGlobal, TokenType};
+use warp::Reply;
+
+fn scan(code: &str) -> Vec<Token> {
+    Scanner::new(code.to_string()).scan().tokens
+}
+
+fn lexemes(tokens: &[Token], token_global: TokenGlobal) -> Vec<String> {
+    tokens.iter()
+        .filter(|token| token.token_global == token_global)
+        .map(|token| token.lexeme.clone())
+        .collect()
+}
+
+fn submit(code: &str) -> Code {
+    Code { code: code.to_string(), language: LanguageConfig::default(), files: Default::default() }
+}
+
+fn token_types(code: &str) -> Vec<TokenType> {
+    scan(code).into_iter().map(|token| token.token_type).collect()
+}
 
 #[test]
 fn scanner_initializes_with_empty_tokens() {
-    let scanner = Scanner::new("".to_string());
-    assert!(scanner.tokens.identifiers.is_empty());
-    assert!(scanner.tokens.symbols.is_empty());
-    assert!(scanner.tokens.reserved_words.is_empty());
-    assert!(scanner.tokens.variables.is_empty());
-    assert!(scanner.tokens.lists.is_empty());
-    assert!(scanner.tokens.comments.is_empty());
-    assert!(scanner.tokens.literals.is_empty());
+    assert!(scan("").is_empty());
+    assert!(scan(" \n\t ").is_empty());
 }
 
 #[test]
 fn scanner_processes_comments_correctly() {
-    let mut scanner = Scanner::new("/* This is a comment */\nint main() { return 0; }".to_string());
-    scanner.process_comments();
-    assert_eq!(scanner.tokens.comments, vec!["/* This is a comment */".to_string()]);
+    let tokens = scan("/* This is a comment */\nint main() { return 0; } // trailing");
+    assert_eq!(tokens.len(), 9);
+    assert_eq!(tokens[0].lexeme, "int");
+    assert_eq!(tokens[0].span.line, 2);
 }
 
 #[test]
 fn scanner_processes_literals_correctly() {
-    let mut scanner = Scanner::new("int main() { return 0; }".to_string());
-    scanner.process_literals();
-    assert_eq!(scanner.tokens.literals, vec![("0".to_string(), "Numeric".to_string())].into_iter().collect());
+    let tokens = scan("int main() { return 0; } \"a // b\" 'c' 2.5 true");
+    assert_eq!(lexemes(&tokens, TokenGlobal::Literal), vec!["0", "\"a // b\"", "'c'", "2.5", "true"]);
 }
 
 #[test]
 fn scanner_processes_symbols_correctly() {
-    let mut scanner = Scanner::new("int main() { return 0; }".to_string());
-    scanner.process_symbols();
-    assert_eq!(scanner.tokens.symbols, vec!["(", ")", "{", "}", ";"].into_iter().map(String::from).collect::<HashSet<_>>());
+    let tokens = scan("int main() { return 0; }");
+    assert_eq!(lexemes(&tokens, TokenGlobal::Symbol), vec!["(", ")", "{", ";", "}"]);
 }
 
 #[test]
 fn scanner_processes_identifiers_and_reserved_words_correctly() {
-    let mut scanner = Scanner::new("int main() { return 0; }".to_string());
-    scanner.process_identifiers_and_reserved_words();
-    assert_eq!(scanner.tokens.identifiers, vec!["int"].into_iter().map(String::from).collect::<HashSet<_>>());
-    assert_eq!(scanner.tokens.reserved_words, vec!["return"].into_iter().map(String::from).collect::<HashSet<_>>());
+    let tokens = scan("int main() { return 0; }");
+    assert_eq!(lexemes(&tokens, TokenGlobal::Identifier), vec!["int"]);
+    assert_eq!(lexemes(&tokens, TokenGlobal::ReservedWord), vec!["return"]);
 }
 
 #[test]
 fn scanner_processes_variables_correctly() {
-    let mut scanner = Scanner::new("int x = 10; int _x = 2; string x6 = 8;".to_string());
-    scanner.process_variables();
-    assert_eq!(scanner.tokens.variables, vec!["x", "_x", "x6"].into_iter().map(String::from).collect::<HashSet<_>>());
+    let tokens = scan("int x = 10; int _x = 2; string x6 = 8;");
+    assert_eq!(lexemes(&tokens, TokenGlobal::Variable), vec!["x", "_x", "x6"]);
 }
 
 #[test]
 fn scanner_processes_lists_correctly() {
-    let mut scanner = Scanner::new("int a[3] {1, 2, 3};".to_string());
-    scanner.process_lists();
-    assert_eq!(scanner.tokens.lists, vec![("int a[3]", "{1, 2, 3} (length: 3)")].into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+    assert_eq!(token_types("int a[3] = {1, 2, 3};"), vec![
+        TokenType::Int, TokenType::Variable, TokenType::OpenBracket, TokenType::IntegerLiteral,
+        TokenType::CloseBracket, TokenType::Assignment, TokenType::OpenBrace, TokenType::IntegerLiteral,
+        TokenType::Comma, TokenType::IntegerLiteral, TokenType::Comma, TokenType::IntegerLiteral,
+        TokenType::CloseBrace, TokenType::Semicolon,
+    ]);
+}
+
+#[test]
+fn scanner_uses_maximal_munch_for_operators() {
+    assert_eq!(token_types("== != <= >= ++ -- += -= && || <<= >> !"), vec![
+        TokenType::Equal, TokenType::NotEqual, TokenType::LessThanOrEqual, TokenType::GreaterThanOrEqual,
+        TokenType::PlusPlus, TokenType::MinusMinus, TokenType::PlusAssignment, TokenType::MinusAssignment,
+        TokenType::LogicalAnd, TokenType::LogicalOr, TokenType::ShiftLeftAssignment, TokenType::ShiftRight,
+        TokenType::LogicalNot,
+    ]);
+    assert_eq!(token_types("i++;x+=1"), vec![
+        TokenType::Variable, TokenType::PlusPlus, TokenType::Semicolon,
+        TokenType::Variable, TokenType::PlusAssignment, TokenType::IntegerLiteral,
+    ]);
+}
+
+#[test]
+fn scanner_tracks_token_spans() {
+    let code = "int x = 1;\n  x += 2;";
+    let tokens = scan(code);
+    let plus_assignment = tokens.iter().find(|token| token.token_type == TokenType::PlusAssignment).unwrap();
+    assert_eq!(plus_assignment.span, Span { start: 15, end: 17, line: 2, column: 5, end_line: 2, end_column: 7 });
+    for token in &tokens {
+        assert_eq!(&code[token.span.start..token.span.end], token.lexeme);
+    }
+}
+
+#[test]
+fn scanner_spans_count_characters_not_bytes() {
+    let tokens = scan("string s = \"héllo\"; s");
+    let last = tokens.last().unwrap();
+    assert_eq!((last.span.start, last.span.column), (21, 21));
+}
+
+#[test]
+fn scanner_emits_comment_tokens_when_enabled() {
+    let options = ScannerOptions { emit_comments: true, ..ScannerOptions::default() };
+    let code = "/* header\n   block */ int x; // note";
+    let tokens = Scanner::with_options(code.to_string(), options).scan().tokens;
+    assert_eq!(lexemes(&tokens, TokenGlobal::Comment), vec!["/* header\n   block */", "// note"]);
+    assert_eq!(tokens[0].token_type, TokenType::Comment);
+    assert_eq!((tokens[0].span.end_line, tokens[0].span.end_column), (2, 12));
+}
+
+#[test]
+fn scanner_round_trips_source_with_trivia() {
+    let options = ScannerOptions { emit_comments: true, emit_whitespace: true, ..ScannerOptions::default() };
+    let code = "int main() {\n\t// say hi\n\tstring s = \"hi\"; /* done */\n}\n";
+    let tokens = Scanner::with_options(code.to_string(), options).scan().tokens;
+    let rebuilt: String = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+    assert_eq!(rebuilt, code);
+}
+
+fn diagnostics(code: &str) -> Vec<(String, String)> {
+    scan(code).into_iter()
+        .filter(|token| token.token_type == TokenType::Error)
+        .map(|token| (token.lexeme, token.diagnostic.unwrap()))
+        .collect()
+}
+
+#[test]
+fn scanner_reports_unterminated_literals() {
+    assert_eq!(diagnostics("string s = \"abc;\nint x;"), vec![
+        ("\"abc;".to_string(), "Unterminated string literal".to_string()),
+    ]);
+    assert_eq!(diagnostics("char c = 'a"), vec![
+        ("'a".to_string(), "Unterminated character literal".to_string()),
+    ]);
+    let tokens = scan("string s = \"abc;\nint x;");
+    assert_eq!(tokens[3].token_type, TokenType::Error);
+    assert_eq!(tokens[4].token_type, TokenType::Int);
+}
+
+#[test]
+fn scanner_reports_invalid_character_literals() {
+    assert_eq!(diagnostics("char c = 'ab'; char d = '';"), vec![
+        ("'ab'".to_string(), "Invalid character literal: expected a single character".to_string()),
+        ("''".to_string(), "Empty character literal".to_string()),
+    ]);
+}
+
+#[test]
+fn scanner_reports_unclosed_block_comments_and_stray_characters() {
+    assert_eq!(diagnostics("int @x = $1; /* never\nclosed"), vec![
+        ("@".to_string(), "Unexpected character '@'".to_string()),
+        ("$".to_string(), "Unexpected character '$'".to_string()),
+        ("/* never\nclosed".to_string(), "Unclosed block comment".to_string()),
+    ]);
+}
+
+#[test]
+fn token_stream_moves_errors_out_of_the_token_stream() {
+    let mut stream = TokenStream::new(Scanner::new("int x = 1 @;".to_string()));
+    assert!(stream.peek_nth(4).is_some_and(|token| token.token_type == TokenType::Semicolon));
+    assert!(stream.peek_nth(5).is_none());
+    let errors = stream.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "Unexpected character '@'");
+    assert_eq!((errors[0].span.column, errors[0].span.end_column), (11, 12));
+}
+
+fn literal(code: &str) -> LiteralValue {
+    let tokens = scan(code);
+    assert_eq!(tokens.len(), 1, "{:?}", tokens);
+    tokens[0].literal.clone().unwrap()
+}
+
+fn integer(value: u64, radix: u32, suffix: &str) -> LiteralValue {
+    LiteralValue::Integer { value, radix, suffix: suffix.to_string() }
+}
+
+fn floating(value: f64, suffix: &str) -> LiteralValue {
+    LiteralValue::Floating { value, suffix: suffix.to_string() }
+}
+
+#[test]
+fn scanner_recognises_integer_literal_forms() {
+    assert_eq!(literal("42"), integer(42, 10, ""));
+    assert_eq!(literal("0x1F"), integer(31, 16, ""));
+    assert_eq!(literal("0b1010"), integer(10, 2, ""));
+    assert_eq!(literal("017"), integer(15, 8, ""));
+    assert_eq!(literal("10u"), integer(10, 10, "u"));
+    assert_eq!(literal("0xFFul"), integer(255, 16, "ul"));
+    assert_eq!(literal("1'000'000"), integer(1_000_000, 10, ""));
+    assert_eq!(literal("0"), integer(0, 10, ""));
+}
+
+#[test]
+fn scanner_recognises_floating_literal_forms() {
+    assert_eq!(literal("3.0f"), floating(3.0, "f"));
+    assert_eq!(literal("1e-3"), floating(0.001, ""));
+    assert_eq!(literal(".5"), floating(0.5, ""));
+    assert_eq!(literal("2.5E+2L"), floating(250.0, "L"));
+    assert_eq!(literal("09.5"), floating(9.5, ""));
+    assert_eq!(scan("1.5")[0].token_type, TokenType::FloatingLiteral);
+}
+
+#[test]
+fn scanner_reports_malformed_numeric_literals() {
+    assert_eq!(diagnostics("0x 09 0b102 12abc 1.5e 1.2.3 99999999999999999999"), vec![
+        ("0x".to_string(), "Invalid hexadecimal literal '0x': expected digits".to_string()),
+        ("09".to_string(), "Invalid digit '9' in octal literal".to_string()),
+        ("0b102".to_string(), "Invalid digit '2' in binary literal".to_string()),
+        ("12abc".to_string(), "Invalid suffix 'abc' on integer literal".to_string()),
+        ("1.5e".to_string(), "Invalid suffix 'e' on floating literal".to_string()),
+        ("1.2.3".to_string(), "Invalid numeric literal '1.2.3'".to_string()),
+        ("99999999999999999999".to_string(), "Integer literal '99999999999999999999' is too large".to_string()),
+    ]);
+}
+
+#[test]
+fn scanner_decodes_escape_sequences() {
+    assert_eq!(literal(r"'\n'"), LiteralValue::Character('\n'));
+    assert_eq!(literal(r"'\''"), LiteralValue::Character('\''));
+    assert_eq!(literal(r"'\x41'"), LiteralValue::Character('A'));
+    assert_eq!(literal(r"'\0'"), LiteralValue::Character('\0'));
+    assert_eq!(literal(r"'\u{1F600}'"), LiteralValue::Character('\u{1F600}'));
+    assert_eq!(literal(r#""tab\there \"quoted\" \\ end""#), LiteralValue::String("tab\there \"quoted\" \\ end".to_string()));
+    let tokens = scan(r#""a\nb""#);
+    assert_eq!(tokens[0].lexeme, r#""a\nb""#);
+}
+
+#[test]
+fn scanner_reports_invalid_escape_sequences() {
+    assert_eq!(diagnostics(r#""bad \q" '\xZZ' '\u{110000}' '\n\n'"#), vec![
+        (r#""bad \q""#.to_string(), r"Invalid escape sequence '\q'".to_string()),
+        (r"'\xZZ'".to_string(), r"Invalid escape sequence '\x': expected hexadecimal digits".to_string()),
+        (r"'\u{110000}'".to_string(), r"Invalid unicode escape '\u{110000}'".to_string()),
+        (r"'\n\n'".to_string(), "Invalid character literal: expected a single character".to_string()),
+    ]);
+}
+
+#[test]
+fn scanner_matches_keywords_exactly() {
+    let tokens = scan("format done ending iffy for do");
+    assert_eq!(lexemes(&tokens, TokenGlobal::Variable), vec!["format", "done", "ending", "iffy"]);
+    assert_eq!(lexemes(&tokens, TokenGlobal::ReservedWord), vec!["for", "do"]);
+    assert_eq!(diagnostics("int héllo = 1;"), vec![
+        ("héllo".to_string(), "Invalid identifier 'héllo'".to_string()),
+    ]);
+}
+
+fn scan_with(code: &str, language: LanguageConfig) -> Vec<Token> {
+    let options = ScannerOptions { language, ..ScannerOptions::default() };
+    Scanner::with_options(code.to_string(), options).scan().tokens
+}
+
+#[test]
+fn scanner_follows_the_selected_dialect() {
+    let code = "string s; bool b = true; int x;";
+    let teaching = scan(code);
+    assert_eq!(lexemes(&teaching, TokenGlobal::Identifier), vec!["string", "bool", "int"]);
+    assert_eq!(lexemes(&teaching, TokenGlobal::Literal), vec!["true"]);
+    let strict = scan_with(code, LanguageConfig { dialect: Dialect::C, ..LanguageConfig::default() });
+    assert_eq!(lexemes(&strict, TokenGlobal::Identifier), vec!["int"]);
+    assert_eq!(lexemes(&strict, TokenGlobal::Variable), vec!["string", "s", "bool", "b", "true", "x"]);
+}
+
+#[test]
+fn scanner_applies_keyword_overrides() {
+    let language = LanguageConfig {
+        dialect: Dialect::C,
+        enable_keywords: vec!["bool".to_string()],
+        disable_keywords: vec!["switch".to_string()],
+    };
+    let tokens = scan_with("bool switch; string s;", language);
+    assert_eq!(lexemes(&tokens, TokenGlobal::Identifier), vec!["bool"]);
+    assert_eq!(lexemes(&tokens, TokenGlobal::Variable), vec!["switch", "string", "s"]);
+}
+
+#[test]
+fn scanner_yields_tokens_lazily() {
+    let mut scanner = Scanner::new("int x = 1; @".to_string());
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Int);
+    assert_eq!(scanner.next().unwrap().lexeme, "x");
+    // Stopping early never reaches the invalid character at the end
+    let first_four: Vec<TokenType> = Scanner::new("a = b; @".to_string()).take(4).map(|token| token.token_type).collect();
+    assert_eq!(first_four, vec![TokenType::Variable, TokenType::Assignment, TokenType::Variable, TokenType::Semicolon]);
+}
+
+#[test]
+fn token_stream_pulls_only_what_is_peeked() {
+    let mut pulled = 0;
+    let counted = Scanner::new("a b c d e".to_string()).inspect(move |_| {
+        pulled += 1;
+        assert!(pulled <= 3);
+    });
+    let mut stream = TokenStream::new(counted);
+    assert_eq!(stream.peek_nth(2).unwrap().lexeme, "c");
+    assert_eq!(stream.buffered(), 3);
+    assert_eq!(stream[0].lexeme, "a");
+}
+
+#[test]
+fn token_stream_drops_released_tokens() {
+    let mut stream = TokenStream::new(Scanner::new("a b c d e".to_string()));
+    assert_eq!(stream.peek_nth(2).unwrap().lexeme, "c");
+    stream.release(2);
+    assert_eq!(stream.held(), 1);
+    // Positions stay the same after the tokens before them are gone
+    assert_eq!(stream[2].lexeme, "c");
+    assert_eq!(stream.peek_nth(4).unwrap().lexeme, "e");
+    assert_eq!((stream.buffered(), stream.held()), (5, 3));
+    // Releasing past what was pulled only drops what is there
+    stream.release(10);
+    assert_eq!((stream.buffered(), stream.held()), (5, 0));
+}
+
+#[test]
+fn analyze_runs_programs_that_only_have_warnings() {
+    let data = analyze(submit("int main() {\n    int i = 0;\n    while (1) {\n        i++;\n        if (i > 3) break;\n    }\n    return 0;\n}")).unwrap();
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["vars"]["i"][1], "4");
+    assert_eq!(json["warnings"][0]["message"], "Warning: This condition is always true");
+
+    // Errors come back together with the warnings found before them
+    let errors = analyze(submit("int main() {\n    while (true) {\n    }\n    return x;\n}")).err().unwrap();
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, ["Use of undeclared variable 'x'", "Warning: This condition is always true"]);
+}
+
+#[test]
+fn analyze_runs_programs_that_read_a_variable_before_assigning_it() {
+    let data = analyze(submit("int main() {\n    int x;\n    x = x + 1;\n    return 0;\n}")).unwrap();
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["vars"]["x"][1], "1");
+    assert_eq!(json["warnings"][0]["message"], "Warning: Variable 'x' is used before being assigned");
+    assert_eq!(json["warnings"][0]["line"], 3);
+}
+
+#[tokio::test]
+async fn handler_replies_with_the_program_state() {
+    let reply = scanning_input_code(submit("int x = 2;\nx *= 3;")).await.unwrap();
+    let body = warp::hyper::body::to_bytes(reply.into_response().into_body()).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["vars"]["x"][1], "6");
 }
@@ -0,0 +1,112 @@
+use crate::parser::{ErrorMessage, Expr, ExprNode, Parser, ProgramNode, Stmt, StmtNode};
+use crate::scanner::Scanner;
+use crate::symbols::{resolve, Scope, ScopeKind, SymbolKind};
+use crate::token::{Span, TokenType};
+
+fn resolve_code(code: &str) -> (Scope, Vec<ErrorMessage>) {
+    let program = Parser::new(Scanner::new(code.to_string())).parse_program().unwrap();
+    resolve(&program)
+}
+
+fn symbol_names(scope: &Scope) -> Vec<&str> {
+    scope.symbols.iter().map(|symbol| symbol.name.as_str()).collect()
+}
+
+#[test]
+fn symbols_form_a_tree_of_scopes() {
+    let code = "int total = 0;\nint square(int n) {\n int r = n * n;\n return r;\n}\n\
+        for (int i = 0; i < 3; i++) {\n int s = square(i);\n total += s;\n}\n\
+        for (int i = 0; i < 2; i++) {\n if (i > 0) {\n float total = 1.5f;\n }\n}";
+    let (global, errors) = resolve_code(code);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(global.kind, ScopeKind::Global);
+    assert_eq!(symbol_names(&global), ["square", "total"]);
+    assert_eq!(global.symbols[0].kind, SymbolKind::Function);
+
+    let kinds: Vec<ScopeKind> = global.children.iter().map(|scope| scope.kind).collect();
+    assert_eq!(kinds, [ScopeKind::Function, ScopeKind::For, ScopeKind::For]);
+    let function = &global.children[0];
+    assert_eq!(symbol_names(function), ["n", "r"]);
+    assert_eq!(function.symbols[0].kind, SymbolKind::Parameter);
+
+    // Each loop declares its own `i`, and its body is a block of its own
+    let first_loop = &global.children[1];
+    assert_eq!(symbol_names(first_loop), ["i"]);
+    assert_eq!(symbol_names(&first_loop.children[0]), ["s"]);
+    let shadowing = &global.children[2].children[0].children[0];
+    assert_eq!(symbol_names(shadowing), ["total"]);
+    assert_eq!(shadowing.symbols[0].symbol_type, TokenType::Float);
+    assert_eq!((shadowing.span.line, shadowing.span.end_line), (11, 13));
+}
+
+#[test]
+fn symbols_report_unresolved_and_duplicate_names() {
+    let code = "int a[2] = {1, 2};\nint f(int x) {\n int x = 1;\n return x;\n}\n\
+        {\n int y = 1;\n}\nint z = y;\nint w = a + f;\nint v = g(1);\nint u = z[0];\nint a = 3;\nint t = w(1);";
+    let (_, errors) = resolve_code(code);
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, [
+        "Variable 'x' already declared",
+        "Use of undeclared variable 'y'",
+        "List 'a' must be indexed",
+        "Function 'f' must be called",
+        "Call to undeclared function 'g'",
+        "List 'z' not found",
+        "Variable 'a' already declared",
+        "'w' is not a function",
+    ]);
+    assert_eq!((errors[1].span.line, errors[1].span.column), (9, 9));
+}
+
+#[test]
+fn symbols_declare_functions_through_prototypes() {
+    let code = "int odd(int n);\nint even(int n) {\n return n == 0 ? 1 : odd(n - 1);\n}\nint odd(int n) {\n return n == 0 ? 0 : even(n - 1);\n}\nint odd(int);";
+    let (global, errors) = resolve_code(code);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(symbol_names(&global), ["even", "odd"]);
+
+    let (_, errors) = resolve_code("int f(int);\nint f(int x) {\n return x;\n}\nint f(int y) {\n return y;\n}\nint g(int);\nint r = g(1);");
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, ["Function 'f' already defined", "Function 'g' is declared but never defined"]);
+}
+
+#[test]
+fn symbols_serialize_for_the_response() {
+    let (global, _) = resolve_code("int x = 1;\n{\n char c = 'a';\n}");
+    let json = serde_json::to_value(&global).unwrap();
+    assert_eq!(json["kind"], "global");
+    assert_eq!(json["symbols"][0]["name"], "x");
+    assert_eq!(json["symbols"][0]["kind"], "variable");
+    assert_eq!(json["symbols"][0]["type"], "Int");
+    assert_eq!(json["children"][0]["kind"], "block");
+    assert_eq!(json["children"][0]["symbols"][0]["line"], 3);
+}
+
+#[test]
+fn symbols_track_variables_that_have_no_value_yet() {
+    let code = "int g;\nint main() {\n int a, b = 2, c;\n int d = a + b;\n a = 1;\n c += a;\n c++;\n int e = g;\n return d;\n}";
+    let (global, errors) = resolve_code(code);
+    let messages: Vec<(&str, &str)> = errors.iter().map(|error| (error.message_type.as_str(), error.message.as_str())).collect();
+    assert_eq!(messages, [
+        ("Warning", "Warning: Variable 'a' is used before being assigned"),
+        ("Warning", "Warning: Variable 'c' is used before being assigned"),
+    ]);
+    assert_eq!((errors[0].span.line, errors[0].span.column), (4, 10));
+
+    assert!(global.symbols.iter().find(|symbol| symbol.name == "g").unwrap().initialized);
+    let main = &global.children[0];
+    let initialized: Vec<(&str, bool)> = main.symbols.iter().map(|symbol| (symbol.name.as_str(), symbol.initialized)).collect();
+    assert_eq!(initialized, [("a", true), ("b", true), ("c", false), ("d", true), ("e", true)]);
+}
+
+#[test]
+fn symbols_stop_at_deeply_nested_expressions() {
+    // Deeper than the parser would ever build
+    let expr = (0..1000).fold(Expr::new(ExprNode::IntLiteral(1), Span::default()), |operand, _| {
+        Expr::new(ExprNode::Unary(TokenType::Minus, Box::new(operand)), Span::default())
+    });
+    let program = ProgramNode { statements: vec![Stmt::new(StmtNode::Expression(expr), Span::default())] };
+    let (_, errors) = resolve(&program);
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    assert_eq!(messages, ["Nesting is too deep"]);
+}
@@ -5,6 +5,7 @@ pub enum TokenType {
     // Reserved Words
     Break,
     Case,
+    Default,
     Do,
     Else,
     For,
@@ -49,12 +50,21 @@ pub enum TokenType {
     LogicalNot,
     BitwiseAnd,
     BitwiseOr,
+    BitwiseXor,
+    BitwiseNot,
+    ShiftLeft,
+    ShiftRight,
     Assignment,
     PlusAssignment,
     MinusAssignment,
     MultiplyAssignment,
     DivideAssignment,
     ModuloAssignment,
+    BitwiseAndAssignment,
+    BitwiseOrAssignment,
+    BitwiseXorAssignment,
+    ShiftLeftAssignment,
+    ShiftRightAssignment,
 
     // Punctuation
     OpenParen,
@@ -69,12 +79,16 @@ pub enum TokenType {
     Exclamation,
     QuestionMark,
     DoubleColon,
+    Dot,
 
     // Special tokens
     Comment,
+    Whitespace,
     Variable,
     List,
     Error,
+    // Stands in for the token after the last one; only the parser makes these
+    Eof,
     PlusPlus,
 }
 
@@ -87,15 +101,51 @@ pub enum TokenGlobal {
     Variable,
     List,
     Comment,
+    Whitespace,
+    Error,
+}
+
+// Byte range into the untouched source plus the 1-based line/column of both ends.
+// Columns count characters, and the end position is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    // The span running from the start of `self` to the end of `end`.
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            end: end.end,
+            end_line: end.end_line,
+            end_column: end.end_column,
+            ..self
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Value of a literal token as computed by the scanner, alongside the raw lexeme.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LiteralValue {
+    Integer { value: u64, radix: u32, suffix: String },
+    Floating { value: f64, suffix: String },
+    // Character and string literals with their escape sequences decoded
+    Character(char),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
     pub token_global: TokenGlobal,
     pub token_type: TokenType,
     pub lexeme: String,
-    pub line: usize,
-    pub column: usize,
-    pub original_line: usize,
-    pub original_column: usize,
+    pub span: Span,
+    pub literal: Option<LiteralValue>,
+    // Set on Error tokens to explain what is wrong with the lexeme
+    pub diagnostic: Option<String>,
 }
@@ -0,0 +1,49 @@
+use crate::token::TokenType;
+
+// The C value types and how they convert, shared by the checker, which works
+// with the types alone, and the interpreter, which converts the values.
+
+// Position in the usual arithmetic conversions char < int < float < double;
+// bool promotes like char. Strings and void are not arithmetic.
+pub(crate) fn rank(value_type: &TokenType) -> Option<u8> {
+    match value_type {
+        TokenType::Char | TokenType::Bool => Some(0),
+        TokenType::Int => Some(1),
+        TokenType::Float => Some(2),
+        TokenType::Double => Some(3),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_integral(value_type: &TokenType) -> bool {
+    rank(value_type).is_some_and(|rank| rank <= 1)
+}
+
+// The common type two arithmetic operands convert to; char and bool compute as int.
+pub(crate) fn arithmetic_type(left: &TokenType, right: &TokenType) -> Option<TokenType> {
+    let rank = rank(left)?.max(rank(right)?).max(1);
+    Some(match rank {
+        1 => TokenType::Int,
+        2 => TokenType::Float,
+        _ => TokenType::Double,
+    })
+}
+
+// Arithmetic and boolean values convert into one another on assignment;
+// strings only ever hold strings.
+pub(crate) fn is_assignable(target: &TokenType, value: &TokenType) -> bool {
+    target == value || (rank(target).is_some() && rank(value).is_some())
+}
+
+// The type name with an article, for diagnostics: "an Integer", "a String"
+pub(crate) fn describe(value_type: &TokenType) -> &'static str {
+    match value_type {
+        TokenType::Int => "an Integer",
+        TokenType::Float => "a Float",
+        TokenType::Double => "a Double",
+        TokenType::Char => "a Char",
+        TokenType::Bool => "a Boolean",
+        TokenType::String => "a String",
+        _ => "a Void",
+    }
+}
@@ -34,11 +34,13 @@ const CodeEditor = () => {
       if (response.ok) { 
         let errors = [];
         if (typeof data === 'string') { 
-          errors = [{ message: data, line: 1, column: 1 }];
+          errors = [{ message: data, line: 1, column: 1, end_line: 1, end_column: 1 }];
         } else if (Array.isArray(data)) { 
           errors = data;
         } else {
           setParserData(data);
+          // The program ran; any warnings are still marked in the editor
+          errors = data.warnings || [];
         }
 
         // console.log("i got into ok")
@@ -79,9 +81,9 @@ const CodeEditor = () => {
 
           return {
             startLineNumber: error.line,
-            startColumn: error.column + 1,
-            endLineNumber: error.line,
-            endColumn: error.column + 1,
+            startColumn: error.column,
+            endLineNumber: error.end_line,
+            endColumn: error.end_column,
             severity: severity,
             message: errorMessage
           };
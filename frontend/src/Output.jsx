@@ -1,5 +1,9 @@
 import { Box, Text } from "@chakra-ui/react";
 
+// Lists of several dimensions arrive as nested arrays, one level per dimension
+const formatList = (values) =>
+    `[${values.map((value) => (Array.isArray(value) ? formatList(value) : value)).join(', ')}]`;
+
 const Output = (props) => {
     const { vars, lists } = props.parserData;
     return (
@@ -17,7 +21,7 @@ const Output = (props) => {
                     <Text key={variable}>{`${variable}: ${value}`}</Text>
                 ))}
                 {Object.entries(lists).map(([list, values]) => (
-                    <Text key={list}>{`${list} [${values.join(', ')}]`}</Text>
+                    <Text key={list}>{`${list} ${formatList(values)}`}</Text>
                 ))}
             </Box>
         </Box>